@@ -0,0 +1,9 @@
+CREATE TABLE websocket_messages (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  session_id INTEGER NOT NULL,
+  room TEXT NOT NULL,
+  payload TEXT NOT NULL,
+  timestamp INTEGER NOT NULL
+);
+
+CREATE INDEX idx_websocket_messages_room_ts ON websocket_messages(room, timestamp);
@@ -0,0 +1 @@
+ALTER TABLE rooms ADD COLUMN deleted_at INTEGER;
@@ -0,0 +1,5 @@
+ALTER TABLE rooms ADD COLUMN updated_at INTEGER NOT NULL DEFAULT 0;
+
+UPDATE rooms SET updated_at = COALESCE(deleted_at, created_at);
+
+CREATE INDEX idx_rooms_updated_at ON rooms(updated_at);
@@ -0,0 +1,56 @@
+-- Timestamps are stored as seconds since the Unix epoch. SQLite can't change
+-- the declared type of a column, so each table is rebuilt; `database::migrate`
+-- disables foreign keys first, so dropping the old tables doesn't cascade.
+CREATE TABLE users_new (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  username TEXT NOT NULL UNIQUE COLLATE NOCASE,
+  password_hash TEXT NOT NULL,
+  created_at INTEGER NOT NULL,
+  avatar_hash TEXT
+);
+
+INSERT INTO users_new (id, username, password_hash, created_at, avatar_hash)
+SELECT id, username, password_hash, CAST(created_at AS INTEGER), avatar_hash FROM users;
+
+DROP TABLE users;
+ALTER TABLE users_new RENAME TO users;
+
+CREATE TABLE rooms_new (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+  owner_id INTEGER NOT NULL,
+  created_at INTEGER NOT NULL,
+  icon_hash TEXT,
+  password_hash TEXT,
+  deleted_at INTEGER,
+  updated_at INTEGER NOT NULL DEFAULT 0,
+  FOREIGN KEY (owner_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+INSERT INTO rooms_new (id, name, owner_id, created_at, icon_hash, password_hash, deleted_at, updated_at)
+SELECT id, name, owner_id, CAST(created_at AS INTEGER), icon_hash, password_hash, deleted_at,
+  CAST(updated_at AS INTEGER)
+FROM rooms;
+
+DROP TABLE rooms;
+ALTER TABLE rooms_new RENAME TO rooms;
+
+CREATE INDEX idx_rooms_updated_at ON rooms(updated_at);
+
+CREATE TABLE messages_new (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  room_id INTEGER NOT NULL,
+  user_id INTEGER NOT NULL,
+  content TEXT NOT NULL,
+  timestamp INTEGER NOT NULL,
+  FOREIGN KEY (room_id) REFERENCES rooms(id) ON DELETE CASCADE,
+  FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+INSERT INTO messages_new (id, room_id, user_id, content, timestamp)
+SELECT id, room_id, user_id, content, CAST(timestamp AS INTEGER) FROM messages;
+
+DROP TABLE messages;
+ALTER TABLE messages_new RENAME TO messages;
+
+CREATE INDEX idx_messages_room_ts ON messages(room_id, timestamp);
@@ -0,0 +1,20 @@
+CREATE VIRTUAL TABLE websocket_messages_fts USING fts5(
+  payload,
+  content = 'websocket_messages',
+  content_rowid = 'id'
+);
+
+INSERT INTO websocket_messages_fts (websocket_messages_fts) VALUES ('rebuild');
+
+CREATE TRIGGER websocket_messages_fts_insert AFTER INSERT ON websocket_messages BEGIN
+  INSERT INTO websocket_messages_fts (rowid, payload) VALUES (new.id, new.payload);
+END;
+
+CREATE TRIGGER websocket_messages_fts_delete AFTER DELETE ON websocket_messages BEGIN
+  INSERT INTO websocket_messages_fts (websocket_messages_fts, rowid, payload) VALUES ('delete', old.id, old.payload);
+END;
+
+CREATE TRIGGER websocket_messages_fts_update AFTER UPDATE OF payload ON websocket_messages BEGIN
+  INSERT INTO websocket_messages_fts (websocket_messages_fts, rowid, payload) VALUES ('delete', old.id, old.payload);
+  INSERT INTO websocket_messages_fts (rowid, payload) VALUES (new.id, new.payload);
+END;
@@ -1,4 +1,20 @@
-mkdir -p "$HOME/.ferroxide"
-rm -fr .sqlx "$HOME/.ferroxide/database.sqlite3"
-sqlite3 "$HOME/.ferroxide/database.sqlite3" < schema.sql
-DATABASE_URL=sqlite:"$HOME"/.ferroxide/database.sqlite3 cargo sqlx prepare
\ No newline at end of file
+# Resolve the base directory the same way util::path does
+if [ -n "$FERROXIDE_HOME" ]; then
+    base="$FERROXIDE_HOME"
+elif [ "$(uname)" != Linux ] || [ -d "$HOME/.ferroxide" ]; then
+    base="$HOME/.ferroxide"
+else
+    case "$XDG_DATA_HOME" in
+        /*) base="$XDG_DATA_HOME/ferroxide" ;;
+        *) base="$HOME/.local/share/ferroxide" ;;
+    esac
+fi
+database="$base/${FERROXIDE_DB_FILE:-database.sqlite3}"
+
+export DATABASE_URL=sqlite:"$database"
+
+mkdir -p "$base"
+rm -fr .sqlx "$database"
+sqlx database create
+sqlx migrate run
+cargo sqlx prepare
@@ -0,0 +1,156 @@
+/// What the process was asked to do on the command line.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command {
+    /// Start the server; the default without arguments.
+    Serve,
+    /// Print the version and exit.
+    Version,
+    /// Print the usage and the recognized environment variables and exit.
+    Help,
+}
+
+pub const HELP: &str = "\
+Usage: ferroxide [--help | --version]
+
+The server is configured through environment variables, which may also be
+set in a .env file in the working directory.
+
+Server:
+  PORT                           TCP port to listen on (2137)
+  FERROXIDE_HOST                 Address to listen on (0.0.0.0)
+  FERROXIDE_UNIX_SOCKET          Listen on this Unix socket instead of TCP
+  FERROXIDE_WORKERS              Worker threads (one per CPU core)
+  FERROXIDE_SHUTDOWN_TIMEOUT     Seconds to drain connections on shutdown (30)
+  FERROXIDE_MAX_BODY             Maximum JSON body size in bytes (1048576)
+  FERROXIDE_COMPRESS             Set to 0 to disable response compression
+  FERROXIDE_TLS_CERT             PEM certificate chain; enables TLS with the key
+  FERROXIDE_TLS_KEY              PEM private key
+  FERROXIDE_RATE_LIMIT           Requests per client and window (unlimited)
+  FERROXIDE_RATE_LIMIT_WINDOW    Rate limit window in seconds (60)
+  FERROXIDE_TZ                   Timezone of local timestamps (Europe/Warsaw)
+  FERROXIDE_HOME                 Data directory (OS specific)
+
+Database:
+  DATABASE_URL                   SQLite URL, overriding FERROXIDE_DB_FILE
+  FERROXIDE_DB_FILE              Database file in the data directory
+  FERROXIDE_DB_MAX_CONNECTIONS   Pool size (5)
+  FERROXIDE_DB_RETRIES           Connection retries on startup (5)
+  FERROXIDE_DB_RETRY_DELAY       Milliseconds before the first retry, then doubling (500)
+  FERROXIDE_DB_WAL               Set to 0 to disable write-ahead logging
+
+Logging:
+  RUST_LOG                       Log filters (info)
+  FERROXIDE_LOG_FILE             Log file (logs.txt in the data directory)
+  FERROXIDE_LOG_FORMAT           text or json
+  FERROXIDE_LOG_STDOUT           Set to 1 to log to stdout instead of stderr
+  FERROXIDE_LOG_SOURCE           Set to 1 to include source locations
+  FERROXIDE_LOG_SYNC             Set to 1 to fsync after every write
+  FERROXIDE_LOG_NO_HEADER        Set to 1 to omit the startup header
+  FERROXIDE_LOG_DAILY            Set to 1 to archive the log file at midnight
+  FERROXIDE_LOG_MAX_LINES        Lines kept in the log file (8192)
+  FERROXIDE_LOG_MAX_BYTES        Rotate by size instead of line count
+  FERROXIDE_LOG_KEEP             Rotated archives to keep (3)
+  FERROXIDE_ACCESS_LOG_FORMAT    common, combined or json
+  FERROXIDE_ACCESS_LOG_SKIP      Paths not to access log (/health,/ready)
+
+CORS:
+  FERROXIDE_CORS_DISABLED        Set to 1 to leave CORS to a reverse proxy
+  FERROXIDE_CORS_ORIGINS         Allowed origins (any)
+  FERROXIDE_CORS_CREDENTIALS     Set to 1 to allow credentials (needs ORIGINS)
+  FERROXIDE_CORS_METHODS         Allowed methods
+  FERROXIDE_CORS_HEADERS         Allowed request headers
+  FERROXIDE_CORS_EXPOSE_HEADERS  Response headers readable by scripts, e.g. X-Total-Count
+  FERROXIDE_CORS_MAX_AGE         Preflight cache lifetime in seconds (3600)
+
+Security headers:
+  FERROXIDE_FRAME_OPTIONS        X-Frame-Options, empty to omit (DENY)
+  FERROXIDE_REFERRER_POLICY      Referrer-Policy, empty to omit (no-referrer)
+  FERROXIDE_CSP                  Content-Security-Policy (none)
+
+WebSocket:
+  FERROXIDE_WS_TOKEN             Token clients must present (none)
+  FERROXIDE_WS_HISTORY           Messages replayed on connect (20)
+  FERROXIDE_WS_MAX_CONNECTIONS   Maximum live sessions (unlimited)
+  FERROXIDE_WS_MAX_FRAME         Maximum message size in bytes (65536)
+  FERROXIDE_WS_PING_INTERVAL     Seconds between pings (5)
+  FERROXIDE_WS_TIMEOUT           Seconds of silence before disconnecting (10)
+
+Options:
+  -h, --help                     Print this help and exit
+  -V, --version                  Print the version and exit
+";
+
+/// The line printed for `--version`.
+pub fn version() -> String {
+    format!("ferroxide {}", env!("CARGO_PKG_VERSION"))
+}
+
+/// Parses the command line arguments, without the program name. Only the
+/// first argument is looked at, as each option makes the process exit.
+///
+/// # Errors
+///
+/// Returns the argument if it is not recognized.
+///
+/// # Examples
+///
+/// ```
+/// assert_eq!(cli::parse(["--version"]), Ok(Command::Version));
+/// assert_eq!(cli::parse(Vec::<String>::new()), Ok(Command::Serve));
+/// ```
+pub fn parse<I, T>(args: I) -> Result<Command, String>
+where
+    I: IntoIterator<Item = T>,
+    T: AsRef<str>,
+{
+    let Some(arg) = args.into_iter().next() else {
+        return Ok(Command::Serve);
+    };
+
+    match arg.as_ref() {
+        "-h" | "--help" => Ok(Command::Help),
+        "-V" | "--version" => Ok(Command::Version),
+        other => Err(other.to_owned()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn version_is_parsed_and_printed() {
+        assert_eq!(parse(["--version"]), Ok(Command::Version));
+        assert_eq!(parse(["-V"]), Ok(Command::Version));
+        assert_eq!(
+            version(),
+            format!("ferroxide {}", env!("CARGO_PKG_VERSION"))
+        );
+    }
+
+    #[test]
+    fn help_is_parsed_and_lists_the_environment() {
+        assert_eq!(parse(["--help", "--version"]), Ok(Command::Help));
+        assert_eq!(parse(["-h"]), Ok(Command::Help));
+
+        assert!(HELP.starts_with("Usage: ferroxide"));
+        for var in [
+            "PORT",
+            "DATABASE_URL",
+            "RUST_LOG",
+            "FERROXIDE_UNIX_SOCKET",
+            "FERROXIDE_LOG_FILE",
+            "FERROXIDE_CORS_ORIGINS",
+            "FERROXIDE_CSP",
+            "FERROXIDE_WS_TOKEN",
+        ] {
+            assert!(HELP.contains(&format!("\n  {var} ")), "{var} is not listed");
+        }
+    }
+
+    #[test]
+    fn no_arguments_serve_and_unknown_ones_are_errors() {
+        assert_eq!(parse(Vec::<String>::new()), Ok(Command::Serve));
+        assert_eq!(parse(["--verbose"]), Err("--verbose".to_owned()));
+    }
+}
@@ -0,0 +1,524 @@
+use std::env;
+use std::fmt::Display;
+use std::net::{IpAddr, Ipv4Addr};
+use std::num::{NonZeroU32, NonZeroU64, NonZeroUsize};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use chrono_tz::Tz;
+
+use crate::util::AccessLogFormat;
+use crate::util::logger::{self, Format};
+
+const DEFAULT_HOST: IpAddr = IpAddr::V4(Ipv4Addr::UNSPECIFIED);
+const DEFAULT_PORT: u16 = 2137;
+const DEFAULT_SHUTDOWN_TIMEOUT: u64 = 30;
+const DEFAULT_MAX_BODY: usize = 1024 * 1024;
+const DEFAULT_DB_FILE: &str = "database.sqlite3";
+const DEFAULT_TIMEZONE: Tz = Tz::Europe__Warsaw;
+const DEFAULT_RATE_LIMIT_WINDOW: u64 = 60;
+const DEFAULT_ACCESS_LOG_SKIP: [&str; 2] = ["/health", "/ready"];
+const DEFAULT_LOG_FILTERS: &str = "info";
+const DEFAULT_LOG_KEEP: usize = 3;
+const DEFAULT_LOG_MAX_LINES: NonZeroUsize = NonZeroUsize::new(logger::MAX_LINES).unwrap();
+const DEFAULT_DB_MAX_CONNECTIONS: NonZeroU32 = NonZeroU32::new(5).unwrap();
+const DEFAULT_DB_RETRIES: u32 = 5;
+const DEFAULT_DB_RETRY_DELAY: u64 = 500;
+const DEFAULT_WS_PING_INTERVAL: NonZeroU64 = NonZeroU64::new(5).unwrap();
+const DEFAULT_WS_TIMEOUT: NonZeroU64 = NonZeroU64::new(10).unwrap();
+const DEFAULT_WS_MAX_FRAME: NonZeroUsize = NonZeroUsize::new(64 * 1024).unwrap();
+const DEFAULT_WS_HISTORY: usize = 20;
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// Settings read from the environment once, on first access.
+///
+/// Invalid values never abort startup: they are replaced by the default and a
+/// description of the problem is kept in [`Config::problems`], so it can be
+/// logged once the logger (which is itself configured from here) is running.
+pub struct Config {
+    /// `FERROXIDE_HOST`, all interfaces by default.
+    pub host: IpAddr,
+    /// `PORT`, 2137 by default.
+    pub port: u16,
+    /// `FERROXIDE_UNIX_SOCKET`, a Unix domain socket to listen on instead of
+    /// the TCP address (Unix only).
+    pub unix_socket: Option<PathBuf>,
+    /// `FERROXIDE_SHUTDOWN_TIMEOUT` in seconds, 30 by default.
+    pub shutdown_timeout: u64,
+    /// `FERROXIDE_WORKERS`; `None` means one worker per CPU core.
+    pub workers: Option<NonZeroUsize>,
+    /// `FERROXIDE_MAX_BODY`, the JSON body limit in bytes, 1 MiB by default.
+    pub max_body: usize,
+    /// Response compression, on unless `FERROXIDE_COMPRESS=0`.
+    pub compress: bool,
+    /// `FERROXIDE_TLS_CERT`, the PEM certificate chain.
+    pub tls_cert: Option<PathBuf>,
+    /// `FERROXIDE_TLS_KEY`, the PEM private key.
+    pub tls_key: Option<PathBuf>,
+    /// `FERROXIDE_HOME`, the base directory overriding the OS-specific one
+    /// (see [`util::try_get_base_path_for`](crate::util::try_get_base_path_for)).
+    pub home: Option<PathBuf>,
+    /// `DATABASE_URL`, taking precedence over [`Config::db_file`].
+    pub database_url: Option<String>,
+    /// `FERROXIDE_DB_FILE`, the database file name in the base directory.
+    pub db_file: String,
+    /// `FERROXIDE_TZ`, the timezone of local timestamps, `Europe/Warsaw` by default.
+    pub timezone: Tz,
+    /// `FERROXIDE_RATE_LIMIT`, requests per window; `None` disables limiting.
+    pub rate_limit: Option<NonZeroUsize>,
+    /// `FERROXIDE_RATE_LIMIT_WINDOW` in seconds, 60 by default.
+    pub rate_limit_window: u64,
+    /// `FERROXIDE_ACCESS_LOG_FORMAT`, `common` by default.
+    pub access_log_format: AccessLogFormat,
+    /// `FERROXIDE_ACCESS_LOG_SKIP`, paths that are not access logged,
+    /// `/health` and `/ready` by default.
+    pub access_log_skip: Vec<String>,
+    pub log: LogConfig,
+    pub db: DbConfig,
+    pub ws: WsConfig,
+    pub cors: CorsConfig,
+    pub security_headers: SecurityHeadersConfig,
+    /// Invalid values that were replaced by their defaults.
+    pub problems: Vec<String>,
+}
+
+/// Settings of [`crate::util::logger`].
+pub struct LogConfig {
+    /// `RUST_LOG`, `info` by default.
+    pub filters: String,
+    /// `FERROXIDE_LOG_FILE`; `None` means `logs.txt` in the base directory.
+    pub file: Option<PathBuf>,
+    /// `FERROXIDE_LOG_FORMAT`, `text` or `json`.
+    pub format: Format,
+    /// Console output to stdout instead of stderr, with `FERROXIDE_LOG_STDOUT=1`.
+    pub stdout: bool,
+    /// Source locations in file records, with `FERROXIDE_LOG_SOURCE=1`.
+    pub source: bool,
+    /// `FERROXIDE_LOG_MAX_LINES`, the number of lines kept when rotating by
+    /// line count, [`logger::MAX_LINES`] by default.
+    pub max_lines: NonZeroUsize,
+    /// `FERROXIDE_LOG_MAX_BYTES`; `None` rotates by line count.
+    pub max_bytes: Option<NonZeroU64>,
+    /// `FERROXIDE_LOG_KEEP`, the number of rotated archives, 3 by default.
+    pub keep: usize,
+    /// fsync after every batch, with `FERROXIDE_LOG_SYNC=1`.
+    pub sync: bool,
+    /// Startup header in the log file, left out with `FERROXIDE_LOG_NO_HEADER=1`.
+    pub header: bool,
+    /// Roll the log file over at local midnight, with `FERROXIDE_LOG_DAILY=1`.
+    pub daily: bool,
+}
+
+/// Settings of [`crate::database::connect`].
+pub struct DbConfig {
+    /// `FERROXIDE_DB_MAX_CONNECTIONS`, the pool size, 5 by default.
+    pub max_connections: NonZeroU32,
+    /// Write-ahead logging, on unless `FERROXIDE_DB_WAL=0`.
+    pub wal: bool,
+    /// `FERROXIDE_DB_RETRIES`, how often a failed connection is retried, 5 by
+    /// default.
+    pub retries: u32,
+    /// `FERROXIDE_DB_RETRY_DELAY` in milliseconds, the wait before the first
+    /// retry, which doubles after each one; 500 ms by default.
+    pub retry_delay: Duration,
+}
+
+/// Settings of the WebSocket sessions, see [`crate::websocket::connect`].
+pub struct WsConfig {
+    /// `FERROXIDE_WS_PING_INTERVAL` in seconds, 5 by default.
+    pub ping_interval: Duration,
+    /// `FERROXIDE_WS_TIMEOUT` in seconds, 10 by default: how long a client may
+    /// stay silent before it is considered gone.
+    pub timeout: Duration,
+    /// `FERROXIDE_WS_MAX_CONNECTIONS`, the maximum number of live sessions;
+    /// `None` means no limit.
+    pub max_connections: Option<NonZeroUsize>,
+    /// `FERROXIDE_WS_MAX_FRAME`, the maximum size in bytes of a message, summed
+    /// over continuation frames, 64 KiB by default.
+    pub max_frame: NonZeroUsize,
+    /// `FERROXIDE_WS_HISTORY`, the number of stored messages replayed to a
+    /// client joining a room, 20 by default.
+    pub history: usize,
+    /// `FERROXIDE_WS_TOKEN`, the token clients must present unless the
+    /// `ws_token` setting overrides it.
+    pub token: Option<String>,
+}
+
+/// Overrides applied by [`crate::util::Cors::new`]; `None` keeps the
+/// middleware's default.
+pub struct CorsConfig {
+    /// `FERROXIDE_CORS_DISABLED=1`
+    pub disabled: bool,
+    /// `FERROXIDE_CORS_ORIGINS`, without trailing slashes.
+    pub origins: Option<Vec<String>>,
+    /// `FERROXIDE_CORS_CREDENTIALS=1`, only honoured along with a non-empty
+    /// [`CorsConfig::origins`].
+    pub credentials: bool,
+    /// `FERROXIDE_CORS_METHODS`
+    pub methods: Option<Vec<String>>,
+    /// `FERROXIDE_CORS_HEADERS`
+    pub headers: Option<Vec<String>>,
+    /// `FERROXIDE_CORS_EXPOSE_HEADERS`
+    pub expose_headers: Option<Vec<String>>,
+    /// `FERROXIDE_CORS_MAX_AGE` in seconds.
+    pub max_age: Option<u32>,
+}
+
+/// Overrides applied by [`crate::util::SecurityHeaders::new`]; `None` keeps
+/// the middleware's default and an empty value omits the header.
+pub struct SecurityHeadersConfig {
+    /// `FERROXIDE_FRAME_OPTIONS`
+    pub frame_options: Option<String>,
+    /// `FERROXIDE_REFERRER_POLICY`
+    pub referrer_policy: Option<String>,
+    /// `FERROXIDE_CSP`
+    pub content_security_policy: Option<String>,
+}
+
+/// Reads variables through `lookup` and records the ones that fail to parse.
+struct Parser<F> {
+    lookup: F,
+    problems: Vec<String>,
+}
+
+impl<F: Fn(&str) -> Option<String>> Parser<F> {
+    fn string(&self, name: &str) -> Option<String> {
+        (self.lookup)(name)
+    }
+
+    /// Whether the variable is set to exactly `1`.
+    fn flag(&self, name: &str) -> bool {
+        self.string(name).is_some_and(|value| value == "1")
+    }
+
+    /// Splits a comma-separated variable into its trimmed, non-empty items.
+    fn list(&self, name: &str) -> Option<Vec<String>> {
+        self.string(name).map(|value| {
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(str::to_owned)
+                .collect()
+        })
+    }
+
+    fn optional<T>(&mut self, name: &str, fallback: &str) -> Option<T>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        match self.string(name)?.parse::<T>() {
+            Ok(value) => Some(value),
+            Err(err) => {
+                self.problems
+                    .push(format!("Invalid {name} value: {err}; {fallback}"));
+                None
+            }
+        }
+    }
+
+    fn or_default<T>(&mut self, name: &str, default: T) -> T
+    where
+        T: FromStr + Display,
+        T::Err: Display,
+    {
+        let fallback = format!("using default of {default}");
+        self.optional(name, &fallback).unwrap_or(default)
+    }
+}
+
+impl Config {
+    /// Parses the configuration from the process environment.
+    pub fn from_env() -> Self {
+        Self::from_lookup(|name| env::var(name).ok())
+    }
+
+    /// Parses the configuration from the variables returned by `lookup`.
+    pub fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Self {
+        let mut parser = Parser {
+            lookup,
+            problems: Vec::new(),
+        };
+
+        let mut rate_limit_window =
+            parser.or_default("FERROXIDE_RATE_LIMIT_WINDOW", DEFAULT_RATE_LIMIT_WINDOW);
+        if rate_limit_window == 0 {
+            parser.problems.push(format!(
+                "Invalid FERROXIDE_RATE_LIMIT_WINDOW value: must be positive; using default of {DEFAULT_RATE_LIMIT_WINDOW}"
+            ));
+            rate_limit_window = DEFAULT_RATE_LIMIT_WINDOW;
+        }
+
+        let access_log_format = match parser.string("FERROXIDE_ACCESS_LOG_FORMAT") {
+            Some(format) => match format.to_ascii_lowercase().as_str() {
+                "common" => AccessLogFormat::Common,
+                "combined" => AccessLogFormat::Combined,
+                "json" => AccessLogFormat::Json,
+                _ => {
+                    parser.problems.push(format!(
+                        "Invalid FERROXIDE_ACCESS_LOG_FORMAT value: {format:?} is not one of common, combined or json; using common"
+                    ));
+                    AccessLogFormat::Common
+                }
+            },
+            None => AccessLogFormat::Common,
+        };
+
+        let log = LogConfig {
+            filters: parser
+                .string("RUST_LOG")
+                .unwrap_or_else(|| DEFAULT_LOG_FILTERS.to_owned()),
+            file: parser
+                .string("FERROXIDE_LOG_FILE")
+                .filter(|path| !path.is_empty())
+                .map(PathBuf::from),
+            format: match parser.string("FERROXIDE_LOG_FORMAT") {
+                Some(format) if format.eq_ignore_ascii_case("json") => Format::Json,
+                _ => Format::Text,
+            },
+            stdout: parser.flag("FERROXIDE_LOG_STDOUT"),
+            source: parser.flag("FERROXIDE_LOG_SOURCE"),
+            max_lines: parser.or_default("FERROXIDE_LOG_MAX_LINES", DEFAULT_LOG_MAX_LINES),
+            max_bytes: parser.optional("FERROXIDE_LOG_MAX_BYTES", "rotating by line count"),
+            keep: parser.or_default("FERROXIDE_LOG_KEEP", DEFAULT_LOG_KEEP),
+            sync: parser.flag("FERROXIDE_LOG_SYNC"),
+            header: !parser.flag("FERROXIDE_LOG_NO_HEADER"),
+            daily: parser.flag("FERROXIDE_LOG_DAILY"),
+        };
+
+        let db = DbConfig {
+            max_connections: parser
+                .or_default("FERROXIDE_DB_MAX_CONNECTIONS", DEFAULT_DB_MAX_CONNECTIONS),
+            wal: parser
+                .string("FERROXIDE_DB_WAL")
+                .is_none_or(|wal| wal != "0"),
+            retries: parser.or_default("FERROXIDE_DB_RETRIES", DEFAULT_DB_RETRIES),
+            retry_delay: Duration::from_millis(
+                parser.or_default("FERROXIDE_DB_RETRY_DELAY", DEFAULT_DB_RETRY_DELAY),
+            ),
+        };
+
+        let ws = WsConfig {
+            ping_interval: Duration::from_secs(
+                parser
+                    .or_default("FERROXIDE_WS_PING_INTERVAL", DEFAULT_WS_PING_INTERVAL)
+                    .get(),
+            ),
+            timeout: Duration::from_secs(
+                parser
+                    .or_default("FERROXIDE_WS_TIMEOUT", DEFAULT_WS_TIMEOUT)
+                    .get(),
+            ),
+            max_connections: parser.optional("FERROXIDE_WS_MAX_CONNECTIONS", "using no limit"),
+            max_frame: parser.or_default("FERROXIDE_WS_MAX_FRAME", DEFAULT_WS_MAX_FRAME),
+            history: parser.or_default("FERROXIDE_WS_HISTORY", DEFAULT_WS_HISTORY),
+            token: parser
+                .string("FERROXIDE_WS_TOKEN")
+                .filter(|token| !token.is_empty()),
+        };
+
+        let origins: Option<Vec<String>> = parser.list("FERROXIDE_CORS_ORIGINS").map(|origins| {
+            origins
+                .into_iter()
+                .map(|origin| origin.trim_end_matches('/').to_owned())
+                .filter(|origin| !origin.is_empty())
+                .collect()
+        });
+
+        // Reflecting every origin along with credentials would let any site
+        // make authenticated requests on a user's behalf
+        let mut credentials = parser.flag("FERROXIDE_CORS_CREDENTIALS");
+        if credentials && origins.as_ref().is_none_or(Vec::is_empty) {
+            parser.problems.push(
+                "FERROXIDE_CORS_CREDENTIALS requires a FERROXIDE_CORS_ORIGINS allowlist; credentials disabled"
+                    .to_owned(),
+            );
+            credentials = false;
+        }
+
+        let cors = CorsConfig {
+            disabled: parser.flag("FERROXIDE_CORS_DISABLED"),
+            origins,
+            credentials,
+            methods: parser.list("FERROXIDE_CORS_METHODS"),
+            headers: parser.list("FERROXIDE_CORS_HEADERS"),
+            expose_headers: parser.list("FERROXIDE_CORS_EXPOSE_HEADERS"),
+            max_age: parser.optional("FERROXIDE_CORS_MAX_AGE", "using the default max age"),
+        };
+
+        let security_headers = SecurityHeadersConfig {
+            frame_options: parser.string("FERROXIDE_FRAME_OPTIONS"),
+            referrer_policy: parser.string("FERROXIDE_REFERRER_POLICY"),
+            content_security_policy: parser.string("FERROXIDE_CSP"),
+        };
+
+        Self {
+            host: parser.or_default("FERROXIDE_HOST", DEFAULT_HOST),
+            port: parser.or_default("PORT", DEFAULT_PORT),
+            unix_socket: parser
+                .string("FERROXIDE_UNIX_SOCKET")
+                .filter(|path| !path.is_empty())
+                .map(PathBuf::from),
+            shutdown_timeout: parser
+                .or_default("FERROXIDE_SHUTDOWN_TIMEOUT", DEFAULT_SHUTDOWN_TIMEOUT),
+            workers: parser.optional("FERROXIDE_WORKERS", "using one worker per CPU core"),
+            max_body: parser.or_default("FERROXIDE_MAX_BODY", DEFAULT_MAX_BODY),
+            compress: parser
+                .string("FERROXIDE_COMPRESS")
+                .is_none_or(|compress| compress != "0"),
+            tls_cert: parser.string("FERROXIDE_TLS_CERT").map(PathBuf::from),
+            tls_key: parser.string("FERROXIDE_TLS_KEY").map(PathBuf::from),
+            home: parser
+                .string("FERROXIDE_HOME")
+                .filter(|path| !path.is_empty())
+                .map(PathBuf::from),
+            database_url: parser.string("DATABASE_URL"),
+            db_file: parser
+                .string("FERROXIDE_DB_FILE")
+                .unwrap_or_else(|| DEFAULT_DB_FILE.to_owned()),
+            timezone: parser.or_default("FERROXIDE_TZ", DEFAULT_TIMEZONE),
+            rate_limit: parser.optional("FERROXIDE_RATE_LIMIT", "rate limiting disabled"),
+            rate_limit_window,
+            access_log_format,
+            access_log_skip: parser
+                .list("FERROXIDE_ACCESS_LOG_SKIP")
+                .unwrap_or_else(|| DEFAULT_ACCESS_LOG_SKIP.map(str::to_owned).to_vec()),
+            log,
+            db,
+            ws,
+            cors,
+            security_headers,
+            problems: parser.problems,
+        }
+    }
+}
+
+#[cfg(test)]
+impl Config {
+    /// Parses the configuration from `vars` alone, as if they were the only
+    /// variables set.
+    pub fn from_vars(vars: &[(&str, &str)]) -> Self {
+        Self::from_lookup(|name| {
+            vars.iter()
+                .find(|(var, _)| *var == name)
+                .map(|(_, value)| (*value).to_owned())
+        })
+    }
+}
+
+/// Returns the process-wide configuration, parsing the environment on the
+/// first call. Variables loaded from `.env` must be in place before that.
+///
+/// # Examples
+///
+/// ```
+/// let config = config::get();
+/// println!("Listening on port {}", config.port);
+/// ```
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(Config::from_env)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bind_host_is_parsed_or_defaulted() {
+        let config = Config::from_vars(&[("FERROXIDE_HOST", "::1"), ("PORT", "8080")]);
+        assert_eq!(config.host, "::1".parse::<IpAddr>().unwrap());
+        assert_eq!(config.port, 8080);
+        assert!(config.problems.is_empty());
+
+        let config = Config::from_vars(&[("FERROXIDE_HOST", "localhost:8080")]);
+        assert_eq!(config.host, DEFAULT_HOST);
+        assert_eq!(config.problems.len(), 1);
+        assert!(config.problems[0].starts_with("Invalid FERROXIDE_HOST value"));
+    }
+
+    #[test]
+    fn defaults_apply_without_variables() {
+        let config = Config::from_vars(&[]);
+        assert_eq!(config.host, DEFAULT_HOST);
+        assert_eq!(config.port, DEFAULT_PORT);
+        assert_eq!(config.home, None);
+        assert_eq!(config.db.retries, DEFAULT_DB_RETRIES);
+        assert_eq!(
+            config.db.retry_delay,
+            Duration::from_millis(DEFAULT_DB_RETRY_DELAY)
+        );
+        assert_eq!(config.database_url, None);
+        assert_eq!(config.db_file, DEFAULT_DB_FILE);
+        assert_eq!(config.timezone, DEFAULT_TIMEZONE);
+        assert_eq!(config.log.filters, DEFAULT_LOG_FILTERS);
+        assert_eq!(config.log.file, None);
+        assert!(!config.log.stdout);
+        assert!(config.log.header);
+        assert!(!config.cors.disabled);
+        assert_eq!(config.cors.origins, None);
+        assert!(config.problems.is_empty());
+    }
+
+    #[test]
+    fn variables_are_parsed_into_typed_fields() {
+        let config = Config::from_vars(&[
+            ("FERROXIDE_HOME", "/srv/ferroxide"),
+            ("FERROXIDE_DB_RETRIES", "2"),
+            ("FERROXIDE_DB_RETRY_DELAY", "50"),
+            ("DATABASE_URL", "sqlite::memory:"),
+            ("FERROXIDE_TZ", "America/New_York"),
+            ("RUST_LOG", "debug,sqlx=warn"),
+            ("FERROXIDE_LOG_FILE", "/var/log/ferroxide.log"),
+            ("FERROXIDE_LOG_STDOUT", "1"),
+            (
+                "FERROXIDE_CORS_ORIGINS",
+                "https://a.example.com/, ,https://b.example.com",
+            ),
+            ("FERROXIDE_CORS_MAX_AGE", "60"),
+        ]);
+        assert_eq!(config.home, Some(PathBuf::from("/srv/ferroxide")));
+        assert_eq!(config.db.retries, 2);
+        assert_eq!(config.db.retry_delay, Duration::from_millis(50));
+        assert_eq!(config.database_url.as_deref(), Some("sqlite::memory:"));
+        assert_eq!(config.timezone, Tz::America__New_York);
+        assert_eq!(config.log.filters, "debug,sqlx=warn");
+        assert_eq!(
+            config.log.file.as_deref(),
+            Some(PathBuf::from("/var/log/ferroxide.log").as_path())
+        );
+        assert!(config.log.stdout);
+        assert_eq!(
+            config.cors.origins.as_deref(),
+            Some(
+                &[
+                    "https://a.example.com".to_owned(),
+                    "https://b.example.com".to_owned()
+                ][..]
+            )
+        );
+        assert_eq!(config.cors.max_age, Some(60));
+        assert!(config.problems.is_empty());
+    }
+
+    #[test]
+    fn invalid_values_fall_back_to_defaults() {
+        let config = Config::from_vars(&[
+            ("PORT", "http"),
+            ("FERROXIDE_TZ", "Mars/Olympus"),
+            ("FERROXIDE_CORS_MAX_AGE", "-1"),
+            ("FERROXIDE_DB_RETRY_DELAY", "soon"),
+        ]);
+        assert_eq!(config.port, DEFAULT_PORT);
+        assert_eq!(config.timezone, DEFAULT_TIMEZONE);
+        assert_eq!(config.cors.max_age, None);
+        assert_eq!(
+            config.db.retry_delay,
+            Duration::from_millis(DEFAULT_DB_RETRY_DELAY)
+        );
+        assert_eq!(config.problems.len(), 4, "{:?}", config.problems);
+    }
+}
@@ -0,0 +1,109 @@
+use sqlx::query_builder::Separated;
+use sqlx::{QueryBuilder, Sqlite, SqlitePool};
+
+/// Rows per `INSERT` statement. With a handful of columns per row this keeps
+/// every statement well below SQLite's bound parameter limit.
+const BATCH_SIZE: usize = 100;
+
+/// A row that can be written by [`insert_many`].
+pub trait Insertable {
+    /// Table the rows are inserted into.
+    const TABLE: &'static str;
+    /// Columns set by [`Insertable::bind_values`], in the same order.
+    const COLUMNS: &'static [&'static str];
+
+    /// Binds one value per column in [`Insertable::COLUMNS`].
+    fn bind_values<'args>(&'args self, row: &mut Separated<'_, 'args, Sqlite, &'static str>);
+}
+
+/// Inserts all `rows` in a single transaction and returns how many were
+/// inserted.
+///
+/// Rather than one statement per row, the rows are written with multi-row
+/// `INSERT ... VALUES (...), (...)` statements of up to 100 rows each. If any
+/// statement fails the transaction is rolled back, so either all rows are
+/// inserted or none.
+///
+/// # Errors
+///
+/// Returns an error if the transaction cannot be started or committed, or if
+/// any of the inserts fails.
+///
+/// # Examples
+///
+/// ```
+/// let inserted = database::insert_many(&pool, &messages).await?;
+/// assert_eq!(inserted, messages.len() as u64);
+/// ```
+#[allow(dead_code)] // Public API for importers; nothing in the server calls it yet
+pub async fn insert_many<T: Insertable>(pool: &SqlitePool, rows: &[T]) -> Result<u64, sqlx::Error> {
+    if rows.is_empty() {
+        return Ok(0);
+    }
+
+    let mut tx = pool.begin().await?;
+    let mut inserted = 0;
+
+    for batch in rows.chunks(BATCH_SIZE) {
+        let mut query = QueryBuilder::<Sqlite>::new(format!(
+            "INSERT INTO {} ({}) ",
+            T::TABLE,
+            T::COLUMNS.join(", ")
+        ));
+
+        query.push_values(batch, |mut row, value| value.bind_values(&mut row));
+        inserted += query.build().execute(&mut *tx).await?.rows_affected();
+    }
+
+    tx.commit().await?;
+    Ok(inserted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database;
+    use crate::model::NewMessage;
+
+    #[actix_web::test]
+    async fn rows_are_inserted_in_batches() {
+        let pool = database::memory_pool().await;
+        let user: i64 = sqlx::query_scalar(
+            "INSERT INTO users (username, password_hash, created_at) VALUES ('alice', '', 0) RETURNING id",
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        let room: i64 = sqlx::query_scalar(
+            "INSERT INTO rooms (name, owner_id, created_at, updated_at) VALUES ('lobby', ?, 0, 0) RETURNING id",
+        )
+        .bind(user)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+
+        let messages: Vec<_> = (0..250)
+            .map(|i| NewMessage {
+                room_id: room,
+                user_id: user,
+                content: format!("message {i}"),
+                timestamp: i,
+            })
+            .collect();
+        assert_eq!(insert_many(&pool, &messages).await.unwrap(), 250);
+
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM messages")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(count, 250);
+
+        // The pool has a single connection, so `changes()` reports the rows
+        // inserted by the last statement: the remainder after two full batches
+        let last: i64 = sqlx::query_scalar("SELECT changes()")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(last, 50);
+    }
+}
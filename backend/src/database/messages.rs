@@ -0,0 +1,68 @@
+use sqlx::SqlitePool;
+
+/// Stores a text message received over a WebSocket session.
+///
+/// `ts` is the time the message was received, in milliseconds since the Unix
+/// epoch (see `tz_time_ms`). Messages are kept for auditing and are not tied
+/// to a user or a room row, since sessions are anonymous.
+///
+/// # Errors
+///
+/// Returns an error if the insert fails.
+///
+/// # Examples
+///
+/// ```
+/// database::insert_message(&pool, session_id, "general", "hello", tz_time_ms()).await?;
+/// ```
+pub async fn insert_message(
+    pool: &SqlitePool,
+    session_id: u64,
+    room: &str,
+    payload: &str,
+    ts: u64,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO websocket_messages (session_id, room, payload, timestamp) VALUES (?, ?, ?, ?)",
+    )
+    .bind(i64::try_from(session_id).unwrap_or(i64::MAX))
+    .bind(room)
+    .bind(payload)
+    .bind(i64::try_from(ts).unwrap_or(i64::MAX))
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Returns the payloads of the last `limit` messages stored for `room`,
+/// oldest first.
+///
+/// # Errors
+///
+/// Returns an error if the query fails.
+///
+/// # Examples
+///
+/// ```
+/// for payload in database::recent_messages(&pool, "general", 20).await? {
+///     println!("{payload}");
+/// }
+/// ```
+pub async fn recent_messages(
+    pool: &SqlitePool,
+    room: &str,
+    limit: usize,
+) -> Result<Vec<String>, sqlx::Error> {
+    let mut payloads: Vec<String> = sqlx::query_scalar(
+        "SELECT payload FROM websocket_messages WHERE room = ? \
+         ORDER BY timestamp DESC, id DESC LIMIT ?",
+    )
+    .bind(room)
+    .bind(i64::try_from(limit).unwrap_or(i64::MAX))
+    .fetch_all(pool)
+    .await?;
+
+    payloads.reverse();
+    Ok(payloads)
+}
@@ -0,0 +1,271 @@
+mod batch;
+mod messages;
+mod search;
+mod settings;
+mod transaction;
+
+pub use batch::*;
+pub use messages::*;
+pub use search::*;
+pub use settings::*;
+pub use transaction::*;
+
+use std::str::FromStr;
+use std::time::Duration;
+
+use sqlx::SqlitePool;
+use sqlx::migrate::MigrateError;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteSynchronous};
+
+use crate::config;
+
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Opens a connection pool to the SQLite database at `url`, configured by
+/// `config`.
+///
+/// Unlike `SqlitePool::connect`, the database file is created if it does not
+/// exist yet, so a fresh deployment can start without any manual setup.
+///
+/// The pool holds at most `FERROXIDE_DB_MAX_CONNECTIONS` connections (5 by
+/// default). Each connection waits up to 5 seconds for a lock held by another
+/// writer before failing with `SQLITE_BUSY`.
+///
+/// Connections use write-ahead logging with `synchronous=NORMAL`, so readers
+/// aren't blocked while a write is in progress, and enforce foreign keys.
+/// Setting `FERROXIDE_DB_WAL=0` keeps SQLite's default rollback journal
+/// instead, with `synchronous=FULL` as that mode needs to stay durable.
+///
+/// A failed connection attempt is retried up to `FERROXIDE_DB_RETRIES` times
+/// (5 by default), waiting `FERROXIDE_DB_RETRY_DELAY` ms (500 by default)
+/// before the first retry and doubling the delay after each one. This rides out slow or briefly unavailable network mounts.
+///
+/// # Errors
+///
+/// Returns an error if `url` is not a valid SQLite URL, or the last error if
+/// the database still cannot be opened after all retries (e.g. because its
+/// directory does not exist).
+///
+/// # Examples
+///
+/// ```
+/// let url = "sqlite:/var/lib/ferroxide/database.sqlite3";
+/// let pool = database::connect(url, &config::get().db).await?;
+/// ```
+pub async fn connect(url: &str, config: &config::DbConfig) -> Result<SqlitePool, sqlx::Error> {
+    let (journal_mode, synchronous) = if config.wal {
+        (SqliteJournalMode::Wal, SqliteSynchronous::Normal)
+    } else {
+        (SqliteJournalMode::Delete, SqliteSynchronous::Full)
+    };
+
+    let options = SqliteConnectOptions::from_str(url)?
+        .create_if_missing(true)
+        .busy_timeout(BUSY_TIMEOUT)
+        .journal_mode(journal_mode)
+        .synchronous(synchronous)
+        .foreign_keys(true);
+
+    let retries = config.retries;
+    let mut delay = config.retry_delay;
+    let mut attempt = 0;
+    loop {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(config.max_connections.get())
+            .connect_with(options.clone())
+            .await;
+
+        match pool {
+            Ok(pool) => return Ok(pool),
+            Err(err) if attempt < retries => {
+                attempt += 1;
+                log::warn!(
+                    "Failed to connect to database: {err}; retrying in {} ms ({attempt}/{retries})",
+                    delay.as_millis()
+                );
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Applies all pending migrations to the database.
+///
+/// The migrations are embedded at compile time from the `migrations/`
+/// directory by `sqlx::migrate!()`, and the ones already applied are tracked
+/// in the `_sqlx_migrations` table, so this is safe to call on every startup.
+///
+/// Foreign keys are not enforced while the migrations run, as SQLite
+/// recommends for schema changes: rebuilding a table drops the old one, which
+/// would otherwise cascade to every table referencing it.
+///
+/// # Errors
+///
+/// Returns a [`MigrateError`] if a migration fails to apply, or if an applied
+/// migration no longer matches its source file.
+///
+/// # Examples
+///
+/// ```
+/// let pool = SqlitePool::connect("sqlite::memory:").await?;
+/// database::migrate(&pool).await?;
+/// ```
+pub async fn migrate(pool: &SqlitePool) -> Result<(), MigrateError> {
+    let mut conn = pool.acquire().await?;
+
+    sqlx::query("PRAGMA foreign_keys = OFF")
+        .execute(&mut *conn)
+        .await?;
+    let res = sqlx::migrate!().run(&mut *conn).await;
+    sqlx::query("PRAGMA foreign_keys = ON")
+        .execute(&mut *conn)
+        .await?;
+
+    res
+}
+
+/// Opens a fresh in-memory database with all migrations applied, for tests.
+///
+/// The pool holds a single connection, as every connection to
+/// `sqlite::memory:` opens a separate, empty database.
+#[cfg(test)]
+pub async fn memory_pool() -> SqlitePool {
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+    migrate(&pool).await.unwrap();
+    pool
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn database_url_can_point_to_memory() {
+        let config = config::Config::from_vars(&[("DATABASE_URL", "sqlite::memory:")]);
+        let url = config.database_url.unwrap();
+
+        let pool = connect(&url, &config.db).await.unwrap();
+        let one: i64 = sqlx::query_scalar("SELECT 1")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(one, 1);
+    }
+
+    #[tokio::test]
+    async fn migrations_create_schema() {
+        let pool = memory_pool().await;
+
+        let tables: Vec<String> =
+            sqlx::query_scalar("SELECT name FROM sqlite_master WHERE type = 'table'")
+                .fetch_all(&pool)
+                .await
+                .unwrap();
+        for table in ["users", "rooms", "rooms_users", "messages", "settings"] {
+            assert!(
+                tables.iter().any(|name| name == table),
+                "{table} is missing"
+            );
+        }
+
+        // Running them again is a no-op
+        migrate(&pool).await.unwrap();
+
+        let foreign_keys: i64 = sqlx::query_scalar("PRAGMA foreign_keys")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(foreign_keys, 1);
+    }
+
+    #[tokio::test]
+    async fn missing_database_file_is_created() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("database.sqlite3");
+        assert!(!path.exists());
+
+        let config = config::Config::from_vars(&[]);
+        let pool = connect(&format!("sqlite:{}", path.display()), &config.db)
+            .await
+            .unwrap();
+        pool.close().await;
+        assert!(path.is_file());
+    }
+
+    #[tokio::test]
+    async fn pool_size_follows_config() {
+        let config = config::Config::from_vars(&[("FERROXIDE_DB_MAX_CONNECTIONS", "3")]);
+        assert_eq!(config.db.max_connections.get(), 3);
+
+        let pool = connect("sqlite::memory:", &config.db).await.unwrap();
+        assert_eq!(pool.options().get_max_connections(), 3);
+
+        let config = config::Config::from_vars(&[("FERROXIDE_DB_MAX_CONNECTIONS", "0")]);
+        assert_eq!(config.db.max_connections.get(), 5);
+    }
+
+    #[tokio::test]
+    async fn connecting_is_retried_until_it_succeeds() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("not-yet");
+        let url = format!("sqlite:{}", missing.join("database.sqlite3").display());
+
+        // The first attempt fails as the directory doesn't exist; it shows up
+        // while the connection waits to retry
+        let created = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            std::fs::create_dir(missing).unwrap();
+        });
+
+        let config = config::Config::from_vars(&[("FERROXIDE_DB_RETRIES", "2")]);
+        let pool = connect(&url, &config.db).await.unwrap();
+        created.await.unwrap();
+        sqlx::query("SELECT 1").execute(&pool).await.unwrap();
+
+        let config = config::Config::from_vars(&[("FERROXIDE_DB_RETRIES", "0")]);
+        let url = format!(
+            "sqlite:{}",
+            dir.path().join("gone/database.sqlite3").display()
+        );
+        assert!(connect(&url, &config.db).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn wal_is_enabled_unless_disabled() {
+        let dir = tempfile::tempdir().unwrap();
+
+        for (wal, journal_mode, synchronous) in [("1", "wal", 1), ("0", "delete", 2)] {
+            let config = config::Config::from_vars(&[("FERROXIDE_DB_WAL", wal)]);
+            let path = dir.path().join(format!("wal-{wal}.sqlite3"));
+            let pool = connect(&format!("sqlite:{}", path.display()), &config.db)
+                .await
+                .unwrap();
+
+            let mode: String = sqlx::query_scalar("PRAGMA journal_mode")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+            assert_eq!(mode, journal_mode);
+
+            // NORMAL with WAL, FULL with the rollback journal
+            let sync: i64 = sqlx::query_scalar("PRAGMA synchronous")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+            assert_eq!(sync, synchronous);
+
+            let foreign_keys: i64 = sqlx::query_scalar("PRAGMA foreign_keys")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+            assert_eq!(foreign_keys, 1);
+            pool.close().await;
+        }
+    }
+}
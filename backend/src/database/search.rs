@@ -0,0 +1,113 @@
+use sqlx::SqlitePool;
+
+use crate::model::WebSocketMessage;
+
+/// Maximum number of matches returned by [`search`].
+const MAX_RESULTS: i64 = 50;
+
+/// Turns user input into an FTS5 query matching messages that contain every
+/// word of it.
+///
+/// Each whitespace-separated word is quoted as an FTS5 string, with embedded
+/// quotes doubled, so operators such as `OR`, `NEAR`, `*`, `^` or column
+/// filters in the input are matched literally instead of being interpreted.
+fn fts_query(query: &str) -> String {
+    query
+        .split_whitespace()
+        .map(|word| format!("\"{}\"", word.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Returns the messages matching all words of `query`, best match first, at
+/// most 50 of them.
+///
+/// Matching uses the `websocket_messages_fts` FTS5 index over message
+/// payloads, which triggers keep in sync with the `websocket_messages` table,
+/// and is ranked by BM25. Words are matched as
+/// whole tokens, case-insensitively; FTS5 syntax in `query` has no special
+/// meaning. A query without any words matches nothing.
+///
+/// # Errors
+///
+/// Returns an error if the query fails.
+///
+/// # Examples
+///
+/// ```
+/// for message in database::search(&pool, "release notes").await? {
+///     println!("{}: {}", message.room, message.payload);
+/// }
+/// ```
+pub async fn search(pool: &SqlitePool, query: &str) -> Result<Vec<WebSocketMessage>, sqlx::Error> {
+    let query = fts_query(query);
+    if query.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    sqlx::query_as(
+        "SELECT m.id, m.session_id, m.room, m.payload, m.timestamp \
+         FROM websocket_messages_fts fts JOIN websocket_messages m ON m.id = fts.rowid \
+         WHERE websocket_messages_fts MATCH ? ORDER BY fts.rank LIMIT ?",
+    )
+    .bind(query)
+    .bind(MAX_RESULTS)
+    .fetch_all(pool)
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database;
+
+    async fn payloads(pool: &SqlitePool, query: &str) -> Vec<String> {
+        search(pool, query)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|message| message.payload)
+            .collect()
+    }
+
+    #[actix_web::test]
+    async fn matches_are_ranked_and_syntax_is_literal() {
+        let pool = database::memory_pool().await;
+        for payload in [
+            "the release notes for the next version are finally up on the website",
+            "lunch anyone?",
+            "Release notes",
+            "release party tonight",
+            "notes OR release",
+        ] {
+            database::insert_message(&pool, 1, "general", payload, 0)
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(
+            payloads(&pool, "release notes").await,
+            [
+                "Release notes",
+                "notes OR release",
+                "the release notes for the next version are finally up on the website",
+            ]
+        );
+        assert_eq!(payloads(&pool, "LUNCH").await, ["lunch anyone?"]);
+
+        // Operators only match themselves
+        assert_eq!(
+            payloads(&pool, "notes OR release").await,
+            ["notes OR release"]
+        );
+        assert!(payloads(&pool, "lun*").await.is_empty());
+        assert!(payloads(&pool, "payload: \"lunch").await.is_empty());
+        assert!(payloads(&pool, "  ").await.is_empty());
+
+        sqlx::query("DELETE FROM websocket_messages WHERE payload = 'lunch anyone?'")
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert!(payloads(&pool, "lunch").await.is_empty());
+    }
+}
@@ -0,0 +1,70 @@
+use sqlx::SqlitePool;
+
+/// Returns the value stored under `key`, or `None` if it was never set.
+///
+/// # Errors
+///
+/// Returns an error if the query fails.
+///
+/// # Examples
+///
+/// ```
+/// let level = database::get_setting(&pool, "log_level").await?;
+/// ```
+pub async fn get_setting(pool: &SqlitePool, key: &str) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar("SELECT value FROM settings WHERE key = ?")
+        .bind(key)
+        .fetch_optional(pool)
+        .await
+}
+
+/// Stores `value` under `key`, replacing any previous value.
+///
+/// # Errors
+///
+/// Returns an error if the query fails.
+///
+/// # Examples
+///
+/// ```
+/// database::set_setting(&pool, "log_level", "debug").await?;
+/// ```
+// Only the dev routes write settings so far
+#[cfg_attr(not(feature = "dev"), allow(dead_code))]
+pub async fn set_setting(pool: &SqlitePool, key: &str, value: &str) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO settings (key, value) VALUES (?, ?) \
+         ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+    )
+    .bind(key)
+    .bind(value)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database;
+
+    #[actix_web::test]
+    async fn settings_are_inserted_overwritten_and_missing() {
+        let pool = database::memory_pool().await;
+        assert_eq!(get_setting(&pool, "log_level").await.unwrap(), None);
+
+        set_setting(&pool, "log_level", "debug").await.unwrap();
+        assert_eq!(
+            get_setting(&pool, "log_level").await.unwrap().as_deref(),
+            Some("debug")
+        );
+
+        set_setting(&pool, "log_level", "warn").await.unwrap();
+        assert_eq!(
+            get_setting(&pool, "log_level").await.unwrap().as_deref(),
+            Some("warn")
+        );
+        assert_eq!(get_setting(&pool, "other").await.unwrap(), None);
+    }
+}
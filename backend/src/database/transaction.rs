@@ -0,0 +1,95 @@
+use futures_util::future::BoxFuture;
+use sqlx::{Sqlite, SqlitePool, Transaction};
+
+/// Runs `f` inside a transaction, committing it if `f` returns `Ok` and
+/// rolling it back if it returns `Err`.
+///
+/// Every query issued through the transaction handed to `f` is part of it,
+/// so a handler doing several writes either applies all of them or none. The
+/// closure returns a boxed future borrowing the transaction, which is most
+/// easily written with `Box::pin(async move { ... })`.
+///
+/// The error type only has to be convertible from [`sqlx::Error`], so
+/// closures can fail with their own errors, e.g. [`ApiError`](crate::routes::ApiError).
+///
+/// # Errors
+///
+/// Returns the error of `f`, or an error if the transaction cannot be
+/// started, committed or rolled back.
+///
+/// # Examples
+///
+/// ```
+/// let id = database::with_transaction(&pool, |tx| {
+///     Box::pin(async move {
+///         sqlx::query("DELETE FROM messages WHERE room = ?").bind(room).execute(&mut **tx).await?;
+///         sqlx::query("DELETE FROM rooms WHERE id = ?").bind(room).execute(&mut **tx).await?;
+///         Ok::<_, sqlx::Error>(room)
+///     })
+/// })
+/// .await?;
+/// ```
+pub async fn with_transaction<F, T, E>(pool: &SqlitePool, f: F) -> Result<T, E>
+where
+    F: for<'c> FnOnce(&'c mut Transaction<'static, Sqlite>) -> BoxFuture<'c, Result<T, E>>,
+    E: From<sqlx::Error>,
+{
+    let mut tx = pool.begin().await?;
+
+    match f(&mut tx).await {
+        Ok(value) => {
+            tx.commit().await?;
+            Ok(value)
+        }
+        Err(err) => {
+            tx.rollback().await?;
+            Err(err)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database;
+
+    async fn count(pool: &SqlitePool) -> i64 {
+        sqlx::query_scalar("SELECT COUNT(*) FROM settings")
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[actix_web::test]
+    async fn failing_closures_are_rolled_back() {
+        let pool = database::memory_pool().await;
+
+        let res = with_transaction(&pool, |tx| {
+            Box::pin(async move {
+                sqlx::query("INSERT INTO settings (key, value) VALUES ('a', '1')")
+                    .execute(&mut **tx)
+                    .await?;
+                // The key is taken, so this fails half-way through
+                sqlx::query("INSERT INTO settings (key, value) VALUES ('a', '2')")
+                    .execute(&mut **tx)
+                    .await?;
+                Ok::<_, sqlx::Error>(())
+            })
+        })
+        .await;
+        assert!(res.is_err());
+        assert_eq!(count(&pool).await, 0);
+
+        with_transaction(&pool, |tx| {
+            Box::pin(async move {
+                sqlx::query("INSERT INTO settings (key, value) VALUES ('a', '1'), ('b', '2')")
+                    .execute(&mut **tx)
+                    .await?;
+                Ok::<_, sqlx::Error>(())
+            })
+        })
+        .await
+        .unwrap();
+        assert_eq!(count(&pool).await, 2);
+    }
+}
@@ -0,0 +1,39 @@
+use serde::Serialize;
+use sqlx::query_builder::Separated;
+use sqlx::{FromRow, Sqlite};
+
+use crate::database::Insertable;
+
+/// A message received over a WebSocket session, as stored by
+/// [`insert_message`](crate::database::insert_message).
+#[derive(Debug, Serialize, FromRow)]
+pub struct WebSocketMessage {
+    pub id: i64,
+    pub session_id: i64,
+    pub room: String,
+    pub payload: String,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: i64,
+}
+
+/// A chat message to be stored, e.g. when importing a room's history.
+#[derive(Debug)]
+pub struct NewMessage {
+    pub room_id: i64,
+    pub user_id: i64,
+    pub content: String,
+    /// Seconds since the Unix epoch.
+    pub timestamp: i64,
+}
+
+impl Insertable for NewMessage {
+    const TABLE: &'static str = "messages";
+    const COLUMNS: &'static [&'static str] = &["room_id", "user_id", "content", "timestamp"];
+
+    fn bind_values<'args>(&'args self, row: &mut Separated<'_, 'args, Sqlite, &'static str>) {
+        row.push_bind(self.room_id)
+            .push_bind(self.user_id)
+            .push_bind(&self.content)
+            .push_bind(self.timestamp);
+    }
+}
@@ -0,0 +1,7 @@
+mod message;
+mod room;
+mod validation;
+
+pub use message::*;
+pub use room::*;
+pub use validation::*;
@@ -0,0 +1,343 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, SqlitePool};
+
+use super::{ValidationError, validate_text};
+use crate::database::with_transaction;
+use crate::util::tz_time_s;
+
+const MAX_NAME_LEN: usize = 64;
+const MAX_ICON_HASH_LEN: usize = 128;
+
+/// A chat room as exposed by the API. The password hash never leaves the
+/// database.
+///
+/// Deleted rooms are kept for auditing, with `deleted_at` set to the time of
+/// deletion, and are hidden from queries unless explicitly included.
+///
+/// `updated_at` is bumped by every change to the room, deletion included, so
+/// clients can sync incrementally.
+#[derive(Debug, Serialize, FromRow)]
+pub struct Room {
+    pub id: i64,
+    pub name: String,
+    pub owner_id: i64,
+    pub created_at: i64,
+    pub icon_hash: Option<String>,
+    pub updated_at: i64,
+    pub deleted_at: Option<i64>,
+}
+
+/// Changes to apply to a room; fields left out are kept as they are.
+#[derive(Debug, Deserialize)]
+pub struct RoomUpdate {
+    pub name: Option<String>,
+    pub icon_hash: Option<String>,
+}
+
+impl RoomUpdate {
+    /// Trims the given fields and checks that none is empty or too long: names
+    /// may be up to 64 characters, icon hashes up to 128.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first field that is invalid.
+    pub fn validate(&mut self) -> Result<(), ValidationError> {
+        if let Some(name) = &mut self.name {
+            validate_text("name", name, MAX_NAME_LEN)?;
+        }
+
+        if let Some(icon_hash) = &mut self.icon_hash {
+            validate_text("icon_hash", icon_hash, MAX_ICON_HASH_LEN)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A room to be created.
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewRoom {
+    pub name: String,
+    pub owner_id: i64,
+    pub icon_hash: Option<String>,
+}
+
+impl NewRoom {
+    /// Trims the given fields and checks them against the same limits as
+    /// [`RoomUpdate::validate`].
+    ///
+    /// # Errors
+    ///
+    /// Returns the first field that is invalid.
+    pub fn validate(&mut self) -> Result<(), ValidationError> {
+        validate_text("name", &mut self.name, MAX_NAME_LEN)?;
+
+        if let Some(icon_hash) = &mut self.icon_hash {
+            validate_text("icon_hash", icon_hash, MAX_ICON_HASH_LEN)?;
+        }
+
+        Ok(())
+    }
+}
+
+const COLUMNS: &str = "id, name, owner_id, created_at, icon_hash, updated_at, deleted_at";
+
+fn now() -> i64 {
+    i64::try_from(tz_time_s()).unwrap_or(i64::MAX)
+}
+
+impl Room {
+    /// Inserts a room, adds its owner as the first member and returns the room
+    /// as stored. Both inserts happen in one transaction.
+    ///
+    /// `created_at` and `updated_at` are both stamped with [`tz_time_s`] here
+    /// rather than left to an SQLite default, so they agree with every other
+    /// timestamp the application writes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a query fails, e.g. because the name is already
+    /// taken or the owner does not exist.
+    pub async fn create(pool: &SqlitePool, room: &NewRoom) -> Result<Room, sqlx::Error> {
+        // The transaction's closure can't borrow from `room`, see `with_transaction`
+        let NewRoom {
+            name,
+            owner_id,
+            icon_hash,
+        } = room.clone();
+
+        with_transaction(pool, move |tx| {
+            Box::pin(async move {
+                let created: Room = sqlx::query_as(&format!(
+                    "INSERT INTO rooms (name, owner_id, icon_hash, created_at, updated_at) \
+                     VALUES (?1, ?2, ?3, ?4, ?4) RETURNING {COLUMNS}"
+                ))
+                .bind(name)
+                .bind(owner_id)
+                .bind(icon_hash)
+                .bind(now())
+                .fetch_one(&mut **tx)
+                .await?;
+
+                sqlx::query("INSERT INTO rooms_users (room_id, user_id) VALUES (?, ?)")
+                    .bind(created.id)
+                    .bind(created.owner_id)
+                    .execute(&mut **tx)
+                    .await?;
+
+                Ok(created)
+            })
+        })
+        .await
+    }
+
+    /// Returns up to `limit` rooms ordered by id, skipping the first `offset`.
+    /// Deleted rooms are only returned if `include_deleted` is set.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the query fails.
+    pub async fn page(
+        pool: &SqlitePool,
+        limit: u32,
+        offset: u32,
+        include_deleted: bool,
+    ) -> Result<Vec<Room>, sqlx::Error> {
+        sqlx::query_as(&format!(
+            "SELECT {COLUMNS} FROM rooms WHERE (? OR deleted_at IS NULL) \
+             ORDER BY id LIMIT ? OFFSET ?"
+        ))
+        .bind(include_deleted)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Returns the total number of rooms, counting deleted ones only if
+    /// `include_deleted` is set.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the query fails.
+    pub async fn count(pool: &SqlitePool, include_deleted: bool) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar("SELECT COUNT(*) FROM rooms WHERE (? OR deleted_at IS NULL)")
+            .bind(include_deleted)
+            .fetch_one(pool)
+            .await
+    }
+
+    /// Returns the room with the given id, or `None` if there is no such room
+    /// or it was deleted and `include_deleted` is not set.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the query fails.
+    pub async fn find(
+        pool: &SqlitePool,
+        id: i64,
+        include_deleted: bool,
+    ) -> Result<Option<Room>, sqlx::Error> {
+        sqlx::query_as(&format!(
+            "SELECT {COLUMNS} FROM rooms WHERE id = ? AND (? OR deleted_at IS NULL)"
+        ))
+        .bind(id)
+        .bind(include_deleted)
+        .fetch_optional(pool)
+        .await
+    }
+
+    /// Returns all rooms changed at or after `since` (seconds since the Unix
+    /// epoch), deleted ones included, ordered by `updated_at`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the query fails.
+    pub async fn changed_since(pool: &SqlitePool, since: i64) -> Result<Vec<Room>, sqlx::Error> {
+        sqlx::query_as(&format!(
+            "SELECT {COLUMNS} FROM rooms WHERE updated_at >= ? ORDER BY updated_at, id"
+        ))
+        .bind(since)
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Applies `changes` to a room that is not deleted and returns the updated
+    /// room, or `None` if there is no such room.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the query fails, e.g. because the new name is
+    /// already taken.
+    pub async fn update(
+        pool: &SqlitePool,
+        id: i64,
+        changes: &RoomUpdate,
+    ) -> Result<Option<Room>, sqlx::Error> {
+        sqlx::query_as(&format!(
+            "UPDATE rooms SET name = COALESCE(?, name), icon_hash = COALESCE(?, icon_hash), \
+             updated_at = ? WHERE id = ? AND deleted_at IS NULL RETURNING {COLUMNS}"
+        ))
+        .bind(&changes.name)
+        .bind(&changes.icon_hash)
+        .bind(now())
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+    }
+
+    /// Marks the room as deleted, stamping `deleted_at` with the current time.
+    ///
+    /// Returns `false` if there is no such room or it was already deleted.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the query fails.
+    pub async fn delete(pool: &SqlitePool, id: i64) -> Result<bool, sqlx::Error> {
+        let res = sqlx::query(
+            "UPDATE rooms SET deleted_at = ?1, updated_at = ?1 WHERE id = ?2 AND deleted_at IS NULL",
+        )
+        .bind(now())
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+        Ok(res.rows_affected() > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database;
+
+    /// Inserts a user and returns its id.
+    async fn insert_user(pool: &SqlitePool, username: &str) -> i64 {
+        sqlx::query_scalar(
+            "INSERT INTO users (username, password_hash, created_at) VALUES (?, '', 0) RETURNING id",
+        )
+        .bind(username)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    fn new_room(name: &str, owner_id: i64) -> NewRoom {
+        NewRoom {
+            name: name.to_owned(),
+            owner_id,
+            icon_hash: None,
+        }
+    }
+
+    #[actix_web::test]
+    async fn deleted_rooms_are_hidden_unless_included() {
+        let pool = database::memory_pool().await;
+        let owner = insert_user(&pool, "alice").await;
+        let kept = Room::create(&pool, &new_room("kept", owner)).await.unwrap();
+        let gone = Room::create(&pool, &new_room("gone", owner)).await.unwrap();
+
+        assert!(Room::delete(&pool, gone.id).await.unwrap());
+        assert!(!Room::delete(&pool, gone.id).await.unwrap());
+
+        assert!(Room::find(&pool, gone.id, false).await.unwrap().is_none());
+        let deleted = Room::find(&pool, gone.id, true).await.unwrap().unwrap();
+        assert!(deleted.deleted_at.is_some());
+
+        let ids = |rooms: Vec<Room>| rooms.into_iter().map(|room| room.id).collect::<Vec<_>>();
+        assert_eq!(
+            ids(Room::page(&pool, 10, 0, false).await.unwrap()),
+            [kept.id]
+        );
+        assert_eq!(
+            ids(Room::page(&pool, 10, 0, true).await.unwrap()),
+            [kept.id, gone.id]
+        );
+        assert_eq!(Room::count(&pool, false).await.unwrap(), 1);
+        assert_eq!(Room::count(&pool, true).await.unwrap(), 2);
+    }
+
+    #[actix_web::test]
+    async fn updates_advance_updated_at() {
+        let pool = database::memory_pool().await;
+        let owner = insert_user(&pool, "alice").await;
+        let id: i64 = sqlx::query_scalar(
+            "INSERT INTO rooms (name, owner_id, created_at, updated_at) VALUES ('lobby', ?, 0, 0) RETURNING id",
+        )
+        .bind(owner)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+
+        let changes = RoomUpdate {
+            name: Some("hall".into()),
+            icon_hash: None,
+        };
+        let room = Room::update(&pool, id, &changes).await.unwrap().unwrap();
+        assert_eq!(room.name, "hall");
+        assert_eq!(room.created_at, 0);
+        assert!(room.updated_at > room.created_at);
+
+        let json = serde_json::to_value(&room).unwrap();
+        assert_eq!(json["updated_at"], room.updated_at);
+    }
+
+    #[actix_web::test]
+    async fn created_at_is_stamped_on_insert() {
+        let pool = database::memory_pool().await;
+        let owner = insert_user(&pool, "alice").await;
+
+        let before = now();
+        let room = Room::create(&pool, &new_room("lobby", owner))
+            .await
+            .unwrap();
+        let after = now();
+        assert!(before <= room.created_at && room.created_at <= after);
+        assert_eq!(room.updated_at, room.created_at);
+
+        let stored = Room::find(&pool, room.id, false).await.unwrap().unwrap();
+        assert_eq!(stored.created_at, room.created_at);
+        let json = serde_json::to_value(&stored).unwrap();
+        assert_eq!(json["created_at"], room.created_at);
+    }
+}
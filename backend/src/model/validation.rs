@@ -0,0 +1,82 @@
+use std::fmt;
+
+/// A user supplied field that failed validation.
+#[derive(Debug)]
+pub struct ValidationError {
+    pub field: &'static str,
+    pub reason: String,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}` {}", self.field, self.reason)
+    }
+}
+
+/// Trims `value` in place and checks that it is neither empty nor longer than
+/// `max` characters.
+pub fn validate_text(
+    field: &'static str,
+    value: &mut String,
+    max: usize,
+) -> Result<(), ValidationError> {
+    let trimmed = value.trim();
+    if trimmed.len() != value.len() {
+        *value = trimmed.to_owned();
+    }
+
+    if value.is_empty() {
+        return Err(ValidationError {
+            field,
+            reason: "must not be empty".to_owned(),
+        });
+    }
+
+    if value.chars().count() > max {
+        return Err(ValidationError {
+            field,
+            reason: format!("must be at most {max} characters long"),
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate(value: &str, max: usize) -> Result<String, String> {
+        let mut value = value.to_owned();
+        validate_text("name", &mut value, max)
+            .map(|()| value)
+            .map_err(|err| err.to_string())
+    }
+
+    #[test]
+    fn surrounding_whitespace_is_trimmed() {
+        assert_eq!(validate("  lobby\n", 8).as_deref(), Ok("lobby"));
+    }
+
+    #[test]
+    fn empty_and_whitespace_only_values_are_rejected() {
+        for value in ["", " \t\n"] {
+            assert_eq!(
+                validate(value, 8).unwrap_err(),
+                "`name` must not be empty",
+                "{value:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn over_length_values_are_rejected() {
+        assert_eq!(validate("12345678", 8).as_deref(), Ok("12345678"));
+        // Characters are counted, not bytes
+        assert_eq!(validate("ééééé", 5).as_deref(), Ok("ééééé"));
+        assert_eq!(
+            validate("123456789", 8).unwrap_err(),
+            "`name` must be at most 8 characters long"
+        );
+    }
+}
@@ -0,0 +1,193 @@
+use std::io;
+
+use actix_web::http::header::{ContentDisposition, ContentType, DispositionParam, DispositionType};
+use actix_web::{HttpResponse, get, post, web};
+use serde::Deserialize;
+use sqlx::SqlitePool;
+use tokio_util::io::ReaderStream;
+
+use super::ApiError;
+use crate::database;
+use crate::util::logger;
+
+const DEFAULT_TAIL_LINES: usize = 100;
+
+/// Setting the log filters chosen through [`set_log_level`] are stored under.
+const LOG_LEVEL_SETTING: &str = "log_level";
+
+#[derive(Deserialize)]
+struct TailQuery {
+    lines: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct LogLevel {
+    level: String,
+}
+
+/// Returns the last `lines` records logged by this process as `text/plain`,
+/// formatted as in the log file.
+///
+/// `lines` defaults to 100 and is capped at [`logger::RECENT_LINES`]. The
+/// records are served from the logger's in-memory buffer rather than the file;
+/// use [`download_logs`] for older ones.
+#[get("/admin/logs")]
+pub async fn tail_logs(query: web::Query<TailQuery>) -> HttpResponse {
+    let lines = query
+        .lines
+        .unwrap_or(DEFAULT_TAIL_LINES)
+        .min(logger::RECENT_LINES);
+
+    let mut tail = logger::recent_lines(lines).join("\n");
+    if !tail.is_empty() {
+        tail.push('\n');
+    }
+
+    HttpResponse::Ok()
+        .content_type(ContentType::plaintext())
+        .body(tail)
+}
+
+/// Streams the whole log file as an attachment, so it can be saved and
+/// attached to a bug report. Responds with `404 Not Found` if the log file
+/// does not exist yet.
+#[get("/admin/logs/download")]
+pub async fn download_logs() -> Result<HttpResponse, ApiError> {
+    let path = logger::log_file_path();
+
+    // Make sure everything logged so far is part of the download
+    if let Err(err) = web::block(logger::flush).await {
+        log::warn!("Failed to flush logs before download: {err}");
+    }
+
+    let file = match tokio::fs::File::open(&path).await {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Err(ApiError::NotFound),
+        Err(err) => {
+            log::error!("Failed to open log file: {err}");
+            return Err(ApiError::Internal);
+        }
+    };
+
+    let filename = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "logs.txt".to_owned());
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::plaintext())
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(filename)],
+        })
+        .streaming(ReaderStream::new(file)))
+}
+
+/// Changes the active log filters without restarting the server.
+///
+/// Expects a JSON body like `{"level":"debug"}`; `level` accepts the same
+/// directives as [`logger::set_filters`], e.g. `info,sqlx=warn`. Only records
+/// emitted afterwards are affected. Responds with `400 Bad Request` if any
+/// directive is invalid.
+///
+/// The filters are saved in the `log_level` setting, which
+/// [`restore_log_level`] applies again on the next start.
+#[post("/admin/log-level")]
+pub async fn set_log_level(
+    pool: web::Data<SqlitePool>,
+    body: web::Json<LogLevel>,
+) -> Result<HttpResponse, ApiError> {
+    logger::set_filters(&body.level).map_err(|err| ApiError::BadRequest(err.to_string()))?;
+    database::set_setting(&pool, LOG_LEVEL_SETTING, &body.level).await?;
+
+    log::info!("Log filters changed to {}", body.level);
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Applies the log filters last set through [`set_log_level`], if any, in
+/// place of `RUST_LOG`. Failures are logged and leave the filters unchanged.
+pub async fn restore_log_level(pool: &SqlitePool) {
+    let level = match database::get_setting(pool, LOG_LEVEL_SETTING).await {
+        Ok(Some(level)) => level,
+        Ok(None) => return,
+        Err(err) => {
+            log::warn!("Failed to read the saved log filters: {err}");
+            return;
+        }
+    };
+
+    match logger::set_filters(&level) {
+        Ok(()) => log::info!("Restored log filters {level}"),
+        Err(err) => log::warn!("Ignoring saved log filters {level:?}: {err}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use actix_web::App;
+    use actix_web::http::header;
+    use actix_web::test::{self, TestRequest};
+
+    use super::*;
+
+    #[actix_web::test]
+    async fn log_file_is_downloaded_as_attachment() {
+        logger::init_for_tests();
+        log::info!("Marker for the download test");
+
+        let app = test::init_service(App::new().service(download_logs)).await;
+        let req = TestRequest::get().uri("/admin/logs/download").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), 200);
+
+        let path = logger::log_file_path();
+        assert!(path.starts_with(std::env::temp_dir()));
+        let filename = path.file_name().unwrap().to_str().unwrap();
+        let disposition = res.headers().get(header::CONTENT_DISPOSITION).unwrap();
+        assert_eq!(
+            disposition.to_str().unwrap(),
+            format!("attachment; filename=\"{filename}\"")
+        );
+
+        let body = test::read_body(res).await;
+        let body = std::str::from_utf8(&body).unwrap();
+        assert!(body.contains("Marker for the download test"));
+
+        // Other tests may have logged more since, but only ever appended
+        assert!(fs::read_to_string(&path).unwrap().starts_with(body));
+    }
+
+    #[actix_web::test]
+    async fn log_level_is_saved_for_the_next_start() {
+        let pool = database::memory_pool().await;
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .service(set_log_level),
+        )
+        .await;
+        let post = |level: &str| {
+            TestRequest::post()
+                .uri("/admin/log-level")
+                .set_json(serde_json::json!({ "level": level }))
+                .to_request()
+        };
+
+        // The default filters, so other tests keep logging as before
+        let res = test::call_service(&app, post("info")).await;
+        assert_eq!(res.status(), 204);
+        let res = test::call_service(&app, post("verbose")).await;
+        assert_eq!(res.status(), 400);
+
+        assert_eq!(
+            database::get_setting(&pool, LOG_LEVEL_SETTING)
+                .await
+                .unwrap()
+                .as_deref(),
+            Some("info")
+        );
+        restore_log_level(&pool).await;
+    }
+}
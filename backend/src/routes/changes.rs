@@ -0,0 +1,138 @@
+use actix_web::{get, web};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+
+use super::{ApiError, Negotiated};
+use crate::model::Room;
+use crate::util::parse_rfc3339;
+
+#[derive(Deserialize)]
+struct ChangesQuery {
+    since: Option<String>,
+}
+
+impl ChangesQuery {
+    /// Returns `since` in seconds since the Unix epoch, 0 if it is missing.
+    fn since(&self) -> Result<i64, ApiError> {
+        let Some(since) = self.since.as_deref().map(str::trim) else {
+            return Ok(0);
+        };
+
+        since
+            .parse::<i64>()
+            .ok()
+            .or_else(|| parse_rfc3339(since).map(|since| since.timestamp()))
+            .ok_or_else(|| {
+                ApiError::BadRequest(
+                    "`since` must be seconds since the Unix epoch or an RFC 3339 timestamp"
+                        .to_owned(),
+                )
+            })
+    }
+}
+
+#[derive(Serialize)]
+struct Change {
+    #[serde(flatten)]
+    room: Room,
+    /// Tombstone telling the client to drop its local copy.
+    deleted: bool,
+}
+
+/// Returns every room created, updated or deleted at or after `since` (seconds
+/// since the Unix epoch, 0 by default), ordered by `updated_at`. `since` may
+/// also be an RFC 3339 timestamp such as `2025-05-17T12:00:00+02:00`; anything
+/// else yields `400 Bad Request`.
+///
+/// Deleted rooms are included with `"deleted": true`. Clients sync by passing
+/// the largest `updated_at` they have seen as the next `since`; as the bound is
+/// inclusive, rooms changed within that second are returned again.
+#[get("/changes")]
+pub async fn changes(
+    pool: web::Data<SqlitePool>,
+    query: web::Query<ChangesQuery>,
+) -> Result<Negotiated<Vec<Change>>, ApiError> {
+    let changes = Room::changed_since(&pool, query.since()?)
+        .await?
+        .into_iter()
+        .map(|room| Change {
+            deleted: room.deleted_at.is_some(),
+            room,
+        })
+        .collect::<Vec<_>>();
+
+    Ok(Negotiated(changes))
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::App;
+    use actix_web::test::{self, TestRequest};
+    use serde_json::Value;
+
+    use super::*;
+    use crate::database;
+
+    #[actix_web::test]
+    async fn new_updated_and_deleted_rooms_are_in_the_delta() {
+        let pool = database::memory_pool().await;
+        let owner: i64 = sqlx::query_scalar(
+            "INSERT INTO users (username, password_hash, created_at) VALUES ('alice', '', 0) RETURNING id",
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        for (name, created_at, updated_at, deleted_at) in [
+            ("unchanged", 100, 100, None),
+            ("updated", 50, 200, None),
+            ("new", 300, 300, None),
+            ("deleted", 50, 250, Some(250)),
+        ] {
+            sqlx::query(
+                "INSERT INTO rooms (name, owner_id, created_at, updated_at, deleted_at) \
+                 VALUES (?, ?, ?, ?, ?)",
+            )
+            .bind(name)
+            .bind(owner)
+            .bind(created_at)
+            .bind(updated_at)
+            .bind(deleted_at)
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool))
+                .configure(crate::routes::configure),
+        )
+        .await;
+
+        for uri in ["/changes?since=200", "/changes?since=1970-01-01T00:03:20Z"] {
+            let req = TestRequest::get().uri(uri).to_request();
+            let delta: Value = test::call_and_read_body_json(&app, req).await;
+            let delta: Vec<_> = delta
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|change| {
+                    (
+                        change["name"].as_str().unwrap(),
+                        change["deleted"].as_bool().unwrap(),
+                    )
+                })
+                .collect();
+            assert_eq!(
+                delta,
+                [("updated", false), ("deleted", true), ("new", false)],
+                "{uri}"
+            );
+        }
+
+        let req = TestRequest::get()
+            .uri("/changes?since=yesterday")
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 400);
+    }
+}
@@ -0,0 +1,236 @@
+use std::fmt;
+
+use actix_web::http::{StatusCode, header};
+use actix_web::{HttpResponse, ResponseError};
+use serde_json::json;
+
+use crate::model::ValidationError;
+
+/// Seconds clients are asked to wait before retrying a request that failed
+/// because the database was busy.
+pub const BUSY_RETRY_AFTER: u32 = 1;
+
+/// Primary result codes of SQLite for a database or table locked by another
+/// connection.
+const SQLITE_BUSY: i32 = 5;
+const SQLITE_LOCKED: i32 = 6;
+
+/// Error returned by API handlers.
+///
+/// Every variant is rendered as the same JSON envelope, so clients only need
+/// to handle a single error shape:
+///
+/// ```json
+/// { "error": { "code": "not_found", "message": "resource not found" } }
+/// ```
+#[derive(Debug)]
+pub enum ApiError {
+    /// The request was malformed; the message says why.
+    BadRequest(String),
+    /// A field of the request body is invalid; the message says which and why.
+    Validation(String),
+    /// Credentials are missing or wrong.
+    Unauthorized,
+    NotFound,
+    /// The request clashes with existing data, e.g. a name that is taken.
+    Conflict,
+    PayloadTooLarge,
+    /// A dependency such as the database is not available right now.
+    Unavailable,
+    /// The database is locked by another writer; the request can be retried
+    /// after [`BUSY_RETRY_AFTER`] seconds, as the `Retry-After` header says.
+    Busy,
+    /// Something failed on our side. Details are logged, not returned.
+    Internal,
+}
+
+impl ApiError {
+    /// Machine readable error code included in the envelope.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::BadRequest(_) => "bad_request",
+            ApiError::Validation(_) => "validation_failed",
+            ApiError::Unauthorized => "unauthorized",
+            ApiError::NotFound => "not_found",
+            ApiError::Conflict => "conflict",
+            ApiError::PayloadTooLarge => "payload_too_large",
+            ApiError::Unavailable => "service_unavailable",
+            ApiError::Busy => "database_busy",
+            ApiError::Internal => "internal_error",
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::BadRequest(message) | ApiError::Validation(message) => f.write_str(message),
+            ApiError::Unauthorized => f.write_str("missing or invalid credentials"),
+            ApiError::NotFound => f.write_str("resource not found"),
+            ApiError::Conflict => f.write_str("resource already exists"),
+            ApiError::PayloadTooLarge => f.write_str("payload too large"),
+            ApiError::Unavailable => f.write_str("service unavailable"),
+            ApiError::Busy => f.write_str("database is busy, retry later"),
+            ApiError::Internal => f.write_str("internal server error"),
+        }
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::Unauthorized => StatusCode::UNAUTHORIZED,
+            ApiError::NotFound => StatusCode::NOT_FOUND,
+            ApiError::Conflict => StatusCode::CONFLICT,
+            ApiError::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            ApiError::Unavailable | ApiError::Busy => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut res = HttpResponse::build(self.status_code());
+        if let ApiError::Busy = self {
+            res.insert_header((header::RETRY_AFTER, BUSY_RETRY_AFTER));
+        }
+
+        res.json(json!({
+            "error": {
+                "code": self.code(),
+                "message": self.to_string(),
+            }
+        }))
+    }
+}
+
+impl From<ValidationError> for ApiError {
+    fn from(err: ValidationError) -> Self {
+        ApiError::Validation(err.to_string())
+    }
+}
+
+/// Whether `err` is SQLite failing with `SQLITE_BUSY` or `SQLITE_LOCKED`,
+/// extended result codes included, i.e. the busy timeout ran out while
+/// another connection held the lock.
+fn is_busy(err: &sqlx::Error) -> bool {
+    let sqlx::Error::Database(db) = err else {
+        return false;
+    };
+
+    db.code()
+        .and_then(|code| code.parse::<i32>().ok())
+        .is_some_and(|code| matches!(code & 0xff, SQLITE_BUSY | SQLITE_LOCKED))
+}
+
+impl From<sqlx::Error> for ApiError {
+    fn from(err: sqlx::Error) -> Self {
+        if let sqlx::Error::Database(db) = &err
+            && db.is_unique_violation()
+        {
+            return ApiError::Conflict;
+        }
+
+        if is_busy(&err) {
+            log::warn!("Database busy: {err}");
+            return ApiError::Busy;
+        }
+
+        log::error!("Database error: {err}");
+        ApiError::Internal
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::{self, TestRequest};
+    use actix_web::{App, web};
+    use serde_json::{Value, json};
+
+    use super::*;
+    use crate::database;
+
+    /// Sends `req` through the API routes and returns the status and body.
+    async fn call(req: TestRequest) -> (StatusCode, Value) {
+        let pool = database::memory_pool().await;
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool))
+                .configure(crate::routes::configure),
+        )
+        .await;
+
+        let res = test::call_service(&app, req.to_request()).await;
+        (res.status(), test::read_body_json(res).await)
+    }
+
+    #[actix_web::test]
+    async fn not_found_uses_the_envelope() {
+        let (status, body) = call(TestRequest::get().uri("/rooms/404")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(
+            body,
+            json!({ "error": { "code": "not_found", "message": "resource not found" } })
+        );
+    }
+
+    #[actix_web::test]
+    async fn validation_errors_use_the_envelope() {
+        let req = TestRequest::post()
+            .uri("/rooms")
+            .set_json(json!({ "name": "", "owner_id": 1 }));
+        let (status, body) = call(req).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["error"]["code"], "validation_failed");
+        assert!(body["error"]["message"].as_str().unwrap().contains("name"));
+    }
+
+    #[actix_web::test]
+    async fn locked_database_is_answered_with_503_and_retry_after() {
+        use std::time::Duration;
+
+        use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions};
+        use sqlx::{Connection, SqliteConnection};
+
+        let dir = tempfile::tempdir().unwrap();
+        let options = SqliteConnectOptions::new()
+            .filename(dir.path().join("database.sqlite3"))
+            .create_if_missing(true)
+            .journal_mode(SqliteJournalMode::Delete)
+            .busy_timeout(Duration::ZERO);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(options.clone())
+            .await
+            .unwrap();
+        database::migrate(&pool).await.unwrap();
+
+        // Another connection holding an exclusive lock keeps out even readers
+        let mut locker = SqliteConnection::connect_with(&options).await.unwrap();
+        sqlx::query("BEGIN EXCLUSIVE")
+            .execute(&mut locker)
+            .await
+            .unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool))
+                .configure(crate::routes::configure),
+        )
+        .await;
+        let res = test::call_service(&app, TestRequest::get().uri("/rooms").to_request()).await;
+
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(
+            res.headers().get(header::RETRY_AFTER).unwrap(),
+            &BUSY_RETRY_AFTER.to_string()
+        );
+        let body: Value = test::read_body_json(res).await;
+        assert_eq!(body["error"]["code"], "database_busy");
+
+        sqlx::query("ROLLBACK").execute(&mut locker).await.unwrap();
+        let res = test::call_service(&app, TestRequest::get().uri("/rooms").to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+}
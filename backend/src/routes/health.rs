@@ -0,0 +1,94 @@
+use actix_web::{HttpResponse, get, web};
+use serde::Serialize;
+use sqlx::SqlitePool;
+
+use super::ApiError;
+use crate::util::{format_duration, tz_time_rfc3339, uptime_ms};
+
+#[derive(Serialize)]
+struct Status {
+    version: &'static str,
+    uptime: String,
+    uptime_ms: u64,
+    time: String,
+}
+
+/// Liveness probe. Always responds with `200 OK` while the server is running.
+#[get("/health")]
+pub async fn health() -> HttpResponse {
+    HttpResponse::Ok().finish()
+}
+
+/// Readiness probe. Responds with `200 OK` if the database answers a trivial
+/// query, and with `503 Service Unavailable` otherwise (e.g. while the pool is
+/// closing during shutdown).
+#[get("/ready")]
+pub async fn ready(pool: web::Data<SqlitePool>) -> Result<HttpResponse, ApiError> {
+    match sqlx::query("SELECT 1").execute(pool.get_ref()).await {
+        Ok(_) => Ok(HttpResponse::Ok().finish()),
+        Err(err) => {
+            log::warn!("Readiness check failed: {err}");
+            Err(ApiError::Unavailable)
+        }
+    }
+}
+
+/// Reports the server version, how long the process has been up (both as a
+/// compact string such as `1h2m3s` and in milliseconds), and the current
+/// server time in the configured timezone.
+#[get("/status")]
+pub async fn status() -> HttpResponse {
+    let uptime_ms = uptime_ms();
+
+    HttpResponse::Ok().json(Status {
+        version: env!("CARGO_PKG_VERSION"),
+        uptime: format_duration(uptime_ms),
+        uptime_ms,
+        time: tz_time_rfc3339(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::{self, TestRequest};
+    use actix_web::{App, http::StatusCode};
+
+    use super::*;
+    use crate::database;
+
+    #[actix_web::test]
+    async fn probes_reflect_pool_state() {
+        let pool = database::memory_pool().await;
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .service(health)
+                .service(ready),
+        )
+        .await;
+
+        for uri in ["/health", "/ready"] {
+            let res = test::call_service(&app, TestRequest::get().uri(uri).to_request()).await;
+            assert_eq!(res.status(), StatusCode::OK, "{uri}");
+        }
+
+        pool.close().await;
+
+        let res = test::call_service(&app, TestRequest::get().uri("/health").to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let res = test::call_service(&app, TestRequest::get().uri("/ready").to_request()).await;
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[actix_web::test]
+    async fn status_reports_uptime_version_and_time() {
+        let app = test::init_service(App::new().service(status)).await;
+        let req = TestRequest::get().uri("/status").to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+
+        assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+        let uptime_ms = body["uptime_ms"].as_u64().unwrap();
+        assert_eq!(body["uptime"], format_duration(uptime_ms));
+        assert!(crate::util::parse_rfc3339(body["time"].as_str().unwrap()).is_some());
+    }
+}
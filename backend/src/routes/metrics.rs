@@ -0,0 +1,42 @@
+use actix_web::{HttpResponse, get};
+
+use crate::util;
+
+/// Exposes the collected request metrics for Prometheus to scrape.
+#[get("/metrics")]
+pub async fn metrics() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(util::render_metrics())
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::App;
+    use actix_web::test::{self, TestRequest};
+
+    use super::*;
+    use crate::routes::health;
+
+    #[actix_web::test]
+    async fn requests_are_counted() {
+        let app = test::init_service(
+            App::new()
+                .wrap(util::Metrics)
+                .service(health)
+                .service(metrics),
+        )
+        .await;
+
+        test::call_service(&app, TestRequest::get().uri("/health").to_request()).await;
+
+        let req = TestRequest::get().uri("/metrics").to_request();
+        let body = test::call_and_read_body(&app, req).await;
+        let body = std::str::from_utf8(&body).unwrap();
+        assert!(
+            body.contains(r#"http_requests_total{method="GET",route="/health",status="200"}"#),
+            "{body}"
+        );
+        assert!(body.contains("http_request_duration_seconds_bucket"));
+    }
+}
@@ -0,0 +1,85 @@
+#[cfg(feature = "dev")]
+mod admin;
+mod changes;
+mod error;
+mod health;
+#[cfg(feature = "metrics")]
+mod metrics;
+mod negotiate;
+mod pagination;
+mod rooms;
+mod search;
+
+#[cfg(feature = "dev")]
+pub use admin::*;
+pub use changes::*;
+pub use error::*;
+pub use health::*;
+#[cfg(feature = "metrics")]
+pub use metrics::*;
+pub use negotiate::*;
+pub use pagination::*;
+pub use rooms::*;
+pub use search::*;
+
+use actix_web::web;
+
+/// Registers every route of the API, including the WebSocket endpoint and
+/// those of enabled features, so that `main` and tests build the same app.
+///
+/// # Examples
+///
+/// ```rust
+/// let app = App::new()
+///     .app_data(pool.clone())
+///     .configure(routes::configure);
+/// ```
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(health)
+        .service(ready)
+        .service(status)
+        .service(list_rooms)
+        .service(create_room)
+        .service(get_room)
+        .service(update_room)
+        .service(delete_room)
+        .service(changes)
+        .service(search)
+        .service(crate::websocket::connect);
+
+    #[cfg(feature = "metrics")]
+    cfg.service(metrics);
+
+    #[cfg(feature = "dev")]
+    cfg.service(tail_logs)
+        .service(download_logs)
+        .service(set_log_level);
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::App;
+    use actix_web::test::{self, TestRequest};
+
+    use super::*;
+    use crate::database;
+
+    #[actix_web::test]
+    async fn configured_app_serves_registered_routes() {
+        let pool = database::memory_pool().await;
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool))
+                .configure(configure),
+        )
+        .await;
+
+        for uri in ["/health", "/ready", "/status", "/rooms", "/changes"] {
+            let res = test::call_service(&app, TestRequest::get().uri(uri).to_request()).await;
+            assert_eq!(res.status(), 200, "{uri}");
+        }
+
+        let req = TestRequest::get().uri("/unknown").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 404);
+    }
+}
@@ -0,0 +1,211 @@
+use actix_web::body::BoxBody;
+use actix_web::http::Method;
+use actix_web::http::header::{self, Accept, ETag, EntityTag, Header, IfNoneMatch};
+use actix_web::{HttpRequest, HttpResponse, Responder, ResponseError};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use super::ApiError;
+
+const JSON: &str = "application/json";
+const MSGPACK: &str = "application/msgpack";
+/// Pre-registration name still sent by many MessagePack clients.
+const X_MSGPACK: &str = "application/x-msgpack";
+
+/// Response body serialized as JSON or MessagePack, whichever the client
+/// prefers according to its `Accept` header.
+///
+/// MessagePack (`application/msgpack` or `application/x-msgpack`) is only
+/// used when the client ranks it above JSON; a missing or unparsable header,
+/// `*/*` and anything else fall back to JSON. Structs are encoded as maps
+/// keyed by field name, so both formats have the same shape. Errors are
+/// always sent as JSON.
+///
+/// Every response carries a strong `ETag` computed from the encoded body. A
+/// `GET` or `HEAD` request whose `If-None-Match` matches it is answered with
+/// `304 Not Modified` and no body, so unchanged resources are not resent.
+///
+/// Extra headers can be added with [`Responder::customize`].
+///
+/// # Examples
+///
+/// ```rust
+/// #[get("/rooms/{id}")]
+/// async fn get_room(...) -> Result<Negotiated<Room>, ApiError> {
+///     Ok(Negotiated(room))
+/// }
+/// ```
+pub struct Negotiated<T>(pub T);
+
+/// Whether the client ranks MessagePack above JSON.
+fn prefers_msgpack(req: &HttpRequest) -> bool {
+    let Ok(accept) = Accept::parse(req) else {
+        return false;
+    };
+
+    accept
+        .ranked()
+        .into_iter()
+        .find_map(|mime| match mime.essence_str() {
+            MSGPACK | X_MSGPACK => Some(true),
+            JSON | "application/*" | "*/*" => Some(false),
+            _ => None,
+        })
+        .unwrap_or(false)
+}
+
+/// Whether the client's `If-None-Match` header matches `etag`, using the weak
+/// comparison RFC 9110 prescribes for it.
+fn not_modified(req: &HttpRequest, etag: &EntityTag) -> bool {
+    match IfNoneMatch::parse(req) {
+        Ok(IfNoneMatch::Any) => true,
+        Ok(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(etag)),
+        Err(_) => false,
+    }
+}
+
+impl<T: Serialize> Responder for Negotiated<T> {
+    type Body = BoxBody;
+
+    fn respond_to(self, req: &HttpRequest) -> HttpResponse<Self::Body> {
+        let (content_type, body) = if prefers_msgpack(req) {
+            (
+                MSGPACK,
+                rmp_serde::to_vec_named(&self.0).map_err(|err| err.to_string()),
+            )
+        } else {
+            (
+                JSON,
+                serde_json::to_vec(&self.0).map_err(|err| err.to_string()),
+            )
+        };
+
+        let body = match body {
+            Ok(body) => body,
+            Err(err) => {
+                log::error!("Failed to serialize {content_type} response: {err}");
+                return ApiError::Internal.error_response();
+            }
+        };
+
+        // The tag covers the encoded bytes, so it differs between JSON and
+        // MessagePack as a strong validator must
+        let etag = EntityTag::new_strong(format!("{:x}", Sha256::digest(&body)));
+
+        let unchanged =
+            matches!(*req.method(), Method::GET | Method::HEAD) && not_modified(req, &etag);
+
+        let mut res = if unchanged {
+            HttpResponse::NotModified()
+        } else {
+            HttpResponse::Ok()
+        };
+
+        // The body depends on the request's Accept header, so caches must too
+        res.insert_header((header::VARY, "Accept"))
+            .insert_header(ETag(etag));
+
+        if unchanged {
+            return res.finish();
+        }
+
+        res.content_type(content_type).body(body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::dev::ServiceResponse;
+    use actix_web::test::{self, TestRequest};
+    use actix_web::{App, web};
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Sample {
+        id: i64,
+        name: String,
+    }
+
+    fn sample() -> Sample {
+        Sample {
+            id: 7,
+            name: "lobby".to_owned(),
+        }
+    }
+
+    /// Calls a route answering with [`sample`] and returns the response.
+    async fn get(req: TestRequest) -> ServiceResponse {
+        let app = test::init_service(
+            App::new().route("/", web::get().to(|| async { Negotiated(sample()) })),
+        )
+        .await;
+        test::call_service(&app, req.uri("/").to_request()).await
+    }
+
+    fn content_type(res: &ServiceResponse) -> &str {
+        res.headers()
+            .get(header::CONTENT_TYPE)
+            .unwrap()
+            .to_str()
+            .unwrap()
+    }
+
+    #[actix_web::test]
+    async fn json_is_sent_by_default() {
+        for accept in [
+            None,
+            Some("*/*"),
+            Some("application/json, application/msgpack;q=0.5"),
+        ] {
+            let mut req = TestRequest::get();
+            if let Some(accept) = accept {
+                req = req.insert_header((header::ACCEPT, accept));
+            }
+
+            let res = get(req).await;
+            assert_eq!(content_type(&res), JSON, "{accept:?}");
+            let body = test::read_body(res).await;
+            assert_eq!(serde_json::from_slice::<Sample>(&body).unwrap(), sample());
+        }
+    }
+
+    #[actix_web::test]
+    async fn msgpack_is_sent_when_preferred() {
+        for accept in [
+            MSGPACK,
+            X_MSGPACK,
+            "application/json;q=0.5, application/msgpack",
+        ] {
+            let res = get(TestRequest::get().insert_header((header::ACCEPT, accept))).await;
+            assert_eq!(content_type(&res), MSGPACK, "{accept}");
+            let body = test::read_body(res).await;
+            assert_eq!(rmp_serde::from_slice::<Sample>(&body).unwrap(), sample());
+        }
+    }
+
+    #[actix_web::test]
+    async fn matching_etags_are_answered_with_304() {
+        let res = get(TestRequest::get()).await;
+        assert_eq!(res.status(), 200);
+        let etag = res.headers().get(header::ETAG).unwrap().clone();
+
+        let res =
+            get(TestRequest::get().insert_header((header::IF_NONE_MATCH, etag.clone()))).await;
+        assert_eq!(res.status(), 304);
+        assert_eq!(res.headers().get(header::ETAG), Some(&etag));
+        assert!(test::read_body(res).await.is_empty());
+
+        // JSON and MessagePack bodies differ, and so do their tags
+        let req = TestRequest::get()
+            .insert_header((header::ACCEPT, MSGPACK))
+            .insert_header((header::IF_NONE_MATCH, etag.clone()));
+        let res = get(req).await;
+        assert_eq!(res.status(), 200);
+        assert_ne!(res.headers().get(header::ETAG), Some(&etag));
+
+        let res = get(TestRequest::get().insert_header((header::IF_NONE_MATCH, "\"stale\""))).await;
+        assert_eq!(res.status(), 200);
+    }
+}
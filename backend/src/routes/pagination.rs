@@ -0,0 +1,23 @@
+use serde::Deserialize;
+
+const DEFAULT_LIMIT: u32 = 50;
+const MAX_LIMIT: u32 = 200;
+
+/// `?limit=` and `?offset=` query parameters of list routes.
+///
+/// `limit` defaults to 50 and is clamped to at most 200; `offset` defaults to 0.
+#[derive(Deserialize)]
+pub struct Pagination {
+    limit: Option<u32>,
+    offset: Option<u32>,
+}
+
+impl Pagination {
+    pub fn limit(&self) -> u32 {
+        self.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT)
+    }
+
+    pub fn offset(&self) -> u32 {
+        self.offset.unwrap_or(0)
+    }
+}
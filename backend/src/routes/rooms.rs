@@ -0,0 +1,187 @@
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, Responder, delete, get, patch, post, web};
+use sqlx::SqlitePool;
+
+use super::{ApiError, Negotiated, Pagination};
+use crate::model::{NewRoom, Room, RoomUpdate};
+
+/// Lists rooms as an array, one page at a time (see [`Pagination`]).
+///
+/// The total number of rooms is returned in the `X-Total-Count` header. For
+/// scripts on other origins to read it, the server has to be started with
+/// `FERROXIDE_CORS_EXPOSE_HEADERS=X-Total-Count`.
+#[get("/rooms")]
+pub async fn list_rooms(
+    pool: web::Data<SqlitePool>,
+    page: web::Query<Pagination>,
+) -> Result<impl Responder, ApiError> {
+    let rooms = Room::page(&pool, page.limit(), page.offset(), false).await?;
+    let total = Room::count(&pool, false).await?;
+
+    Ok(Negotiated(rooms)
+        .customize()
+        .insert_header(("X-Total-Count", total)))
+}
+
+/// Creates a room, responding with `201 Created` and the new room. Its owner
+/// becomes the room's first member. Invalid fields (see [`NewRoom::validate`])
+/// or an owner that does not exist yield `422 Unprocessable Entity`, and a
+/// name that is already taken `409 Conflict`.
+#[post("/rooms")]
+pub async fn create_room(
+    pool: web::Data<SqlitePool>,
+    body: web::Json<NewRoom>,
+) -> Result<impl Responder, ApiError> {
+    let mut room = body.into_inner();
+    room.validate()?;
+
+    let room = Room::create(&pool, &room).await.map_err(|err| match &err {
+        sqlx::Error::Database(db) if db.is_foreign_key_violation() => {
+            ApiError::Validation("`owner_id` must refer to an existing user".into())
+        }
+        _ => ApiError::from(err),
+    })?;
+
+    Ok(Negotiated(room)
+        .customize()
+        .with_status(StatusCode::CREATED))
+}
+
+/// Returns a single room, or `404 Not Found` if it does not exist.
+#[get("/rooms/{id}")]
+pub async fn get_room(
+    pool: web::Data<SqlitePool>,
+    id: web::Path<i64>,
+) -> Result<Negotiated<Room>, ApiError> {
+    match Room::find(&pool, id.into_inner(), false).await? {
+        Some(room) => Ok(Negotiated(room)),
+        None => Err(ApiError::NotFound),
+    }
+}
+
+/// Renames a room or changes its icon, responding with the updated room, or
+/// `404 Not Found` if it does not exist. Invalid fields (see
+/// [`RoomUpdate::validate`]) yield `422 Unprocessable Entity`, and a name that
+/// is already taken `409 Conflict`.
+#[patch("/rooms/{id}")]
+pub async fn update_room(
+    pool: web::Data<SqlitePool>,
+    id: web::Path<i64>,
+    body: web::Json<RoomUpdate>,
+) -> Result<Negotiated<Room>, ApiError> {
+    let mut changes = body.into_inner();
+    changes.validate()?;
+
+    match Room::update(&pool, id.into_inner(), &changes).await? {
+        Some(room) => Ok(Negotiated(room)),
+        None => Err(ApiError::NotFound),
+    }
+}
+
+/// Deletes a room, responding with `204 No Content`, or `404 Not Found` if it
+/// does not exist. The room is only marked as deleted, see [`Room`].
+#[delete("/rooms/{id}")]
+pub async fn delete_room(
+    pool: web::Data<SqlitePool>,
+    id: web::Path<i64>,
+) -> Result<HttpResponse, ApiError> {
+    if Room::delete(&pool, id.into_inner()).await? {
+        Ok(HttpResponse::NoContent().finish())
+    } else {
+        Err(ApiError::NotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::App;
+    use actix_web::test::{self, TestRequest};
+    use serde_json::Value;
+
+    use super::*;
+    use crate::database;
+
+    /// Inserts a user and returns its id.
+    async fn insert_user(pool: &SqlitePool, username: &str) -> i64 {
+        sqlx::query_scalar(
+            "INSERT INTO users (username, password_hash, created_at) VALUES (?, '', 0) RETURNING id",
+        )
+        .bind(username)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    #[actix_web::test]
+    async fn rooms_are_read_from_the_pool() {
+        let pool = database::memory_pool().await;
+        let owner = insert_user(&pool, "alice").await;
+        let id: i64 = sqlx::query_scalar(
+            "INSERT INTO rooms (name, owner_id, created_at, updated_at) VALUES ('lobby', ?, 0, 0) RETURNING id",
+        )
+        .bind(owner)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool))
+                .configure(crate::routes::configure),
+        )
+        .await;
+
+        let req = TestRequest::get().uri(&format!("/rooms/{id}")).to_request();
+        let room: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(room["name"], "lobby");
+        assert_eq!(room["owner_id"], owner);
+
+        let req = TestRequest::get().uri("/rooms").to_request();
+        let rooms: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(rooms.as_array().unwrap().len(), 1);
+
+        let req = TestRequest::get().uri("/rooms/404").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 404);
+    }
+
+    #[actix_web::test]
+    async fn rooms_are_paginated() {
+        let pool = database::memory_pool().await;
+        let owner = insert_user(&pool, "alice").await;
+        for i in 0..250 {
+            sqlx::query(
+                "INSERT INTO rooms (name, owner_id, created_at, updated_at) VALUES (?, ?, 0, 0)",
+            )
+            .bind(format!("room-{i}"))
+            .bind(owner)
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool))
+                .configure(crate::routes::configure),
+        )
+        .await;
+
+        for (uri, len) in [
+            ("/rooms", 50),
+            ("/rooms?limit=10&offset=245", 5),
+            ("/rooms?limit=1000", 200),
+        ] {
+            let res = test::call_service(&app, TestRequest::get().uri(uri).to_request()).await;
+            assert_eq!(res.headers().get("X-Total-Count").unwrap(), "250", "{uri}");
+            let rooms: Value = test::read_body_json(res).await;
+            assert_eq!(rooms.as_array().unwrap().len(), len, "{uri}");
+        }
+
+        let req = TestRequest::get()
+            .uri("/rooms?limit=2&offset=3")
+            .to_request();
+        let rooms: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(rooms[0]["name"], "room-3");
+        assert_eq!(rooms[1]["name"], "room-4");
+    }
+}
@@ -0,0 +1,28 @@
+use actix_web::{get, web};
+use serde::Deserialize;
+use sqlx::SqlitePool;
+
+use super::{ApiError, Negotiated};
+use crate::database;
+use crate::model::WebSocketMessage;
+
+#[derive(Deserialize)]
+struct SearchQuery {
+    #[serde(default)]
+    q: String,
+}
+
+/// Returns up to 50 messages containing every word of `q`, best match first
+/// (see [`database::search`]). A missing or blank `q` yields
+/// `400 Bad Request`.
+#[get("/search")]
+pub async fn search(
+    pool: web::Data<SqlitePool>,
+    query: web::Query<SearchQuery>,
+) -> Result<Negotiated<Vec<WebSocketMessage>>, ApiError> {
+    if query.q.trim().is_empty() {
+        return Err(ApiError::BadRequest("query must not be empty".to_owned()));
+    }
+
+    Ok(Negotiated(database::search(&pool, &query.q).await?))
+}
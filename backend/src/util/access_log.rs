@@ -0,0 +1,312 @@
+use std::future::{Ready, ready};
+use std::time::Instant;
+
+use actix_web::HttpMessage;
+use actix_web::body::{BodySize, MessageBody};
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform, forward_ready};
+use actix_web::error::Error;
+use actix_web::http::{StatusCode, header};
+use futures_util::future::LocalBoxFuture;
+
+use super::{RequestId, tz_time};
+use crate::config;
+
+/// Layout of the access log lines.
+#[derive(Clone, Copy)]
+pub enum AccessLogFormat {
+    /// NCSA common log format with the duration in milliseconds appended:
+    /// `{ip} - - [{date}] "{request line}" {status} {bytes} {ms}ms`
+    Common,
+    /// The common format plus the quoted `Referer` and `User-Agent` headers.
+    Combined,
+    /// One JSON object with `method`, `path`, `status`, `duration_ms`, `ip`,
+    /// `bytes` and `request_id` fields.
+    Json,
+}
+
+/// The parts of a request that are logged, captured before it is handed to
+/// the inner service.
+struct RequestInfo {
+    ip: String,
+    method: String,
+    path: String,
+    version: String,
+    referer: String,
+    user_agent: String,
+    request_id: Option<String>,
+}
+
+impl RequestInfo {
+    fn new(req: &ServiceRequest) -> Self {
+        let header = |name| {
+            req.headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or("-")
+                .to_owned()
+        };
+
+        Self {
+            ip: req
+                .peer_addr()
+                .map_or_else(|| "-".to_owned(), |addr| addr.ip().to_string()),
+            method: req.method().to_string(),
+            path: req
+                .uri()
+                .path_and_query()
+                .map_or_else(|| req.path().to_owned(), ToString::to_string),
+            version: format!("{:?}", req.version()),
+            referer: header(header::REFERER),
+            user_agent: header(header::USER_AGENT),
+            request_id: req.extensions().get::<RequestId>().map(|id| id.0.clone()),
+        }
+    }
+
+    fn line(
+        &self,
+        format: AccessLogFormat,
+        status: StatusCode,
+        bytes: Option<u64>,
+        ms: u128,
+    ) -> String {
+        let Self {
+            ip,
+            method,
+            path,
+            version,
+            referer,
+            user_agent,
+            request_id,
+        } = self;
+        let status = status.as_u16();
+
+        match format {
+            AccessLogFormat::Common | AccessLogFormat::Combined => {
+                let date = tz_time().format("%d/%b/%Y:%H:%M:%S %z");
+                let bytes = bytes.map_or_else(|| "-".to_owned(), |bytes| bytes.to_string());
+                let mut line = format!(
+                    "{ip} - - [{date}] \"{method} {path} {version}\" {status} {bytes} {ms}ms"
+                );
+                if let AccessLogFormat::Combined = format {
+                    line.push_str(&format!(" {referer:?} {user_agent:?}"));
+                }
+                line
+            }
+            AccessLogFormat::Json => serde_json::json!({
+                "method": method,
+                "path": path,
+                "status": status,
+                "duration_ms": ms,
+                "ip": ip,
+                "bytes": bytes,
+                "request_id": request_id,
+            })
+            .to_string(),
+        }
+    }
+}
+
+/// Middleware logging one line per request, with its method, path, status,
+/// duration and client IP.
+///
+/// Lines are emitted at info level under the `access` target, so they end up
+/// in the same file as every other record and can be filtered separately,
+/// e.g. with `RUST_LOG=info,access=off`. The layout is chosen with
+/// `FERROXIDE_ACCESS_LOG_FORMAT` (`common`, the default, `combined` or
+/// `json`; see [`AccessLogFormat`]).
+///
+/// Requests to the paths listed in `FERROXIDE_ACCESS_LOG_SKIP` (comma-separated
+/// and matched exactly, `/health` and `/ready` by default) are not logged, so
+/// frequent probes don't drown out real traffic. Set it to an empty value to
+/// log everything.
+///
+/// Like the rate limiter, the client IP is the peer address of the
+/// connection. The middleware should be wrapped inside
+/// [`RequestIdentifier`](super::RequestIdentifier) for the lines to carry the
+/// request id, which JSON lines also include as a `request_id` field.
+pub struct AccessLog;
+
+/// Whether requests to `path` are left out of the access log, i.e. it is one
+/// of the `skip` paths. The query string is not part of `path`.
+fn is_skipped(skip: &[String], path: &str) -> bool {
+    skip.iter().any(|skipped| skipped == path)
+}
+
+pub struct AccessLogMiddleware<S> {
+    service: S,
+    format: AccessLogFormat,
+    skip: &'static [String],
+}
+
+impl<S, B> Transform<S, ServiceRequest> for AccessLog
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = AccessLogMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(AccessLogMiddleware {
+            service,
+            format: config::get().access_log_format,
+            skip: &config::get().access_log_skip,
+        }))
+    }
+}
+
+impl<S, B> Service<ServiceRequest> for AccessLogMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<ServiceResponse<B>, Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        if is_skipped(self.skip, req.path()) {
+            return Box::pin(self.service.call(req));
+        }
+
+        let start = Instant::now();
+        let info = RequestInfo::new(&req);
+        let format = self.format;
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let res = fut.await;
+            let (status, bytes) = match &res {
+                Ok(res) => {
+                    let bytes = match res.response().body().size() {
+                        BodySize::Sized(bytes) => Some(bytes),
+                        BodySize::None | BodySize::Stream => None,
+                    };
+                    (res.status(), bytes)
+                }
+                Err(err) => (err.as_response_error().status_code(), None),
+            };
+
+            let ms = start.elapsed().as_millis();
+            log::info!(target: "access", "{}", info.line(format, status, bytes, ms));
+
+            res
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use actix_web::test::{self, TestRequest};
+    use actix_web::{App, HttpResponse, web};
+
+    use super::*;
+    use crate::util::{RequestIdentifier, logger};
+
+    #[actix_web::test]
+    async fn requests_are_logged_to_the_file_sink() {
+        logger::init_for_tests();
+
+        let app = test::init_service(
+            App::new()
+                .wrap(AccessLog)
+                .wrap(RequestIdentifier)
+                .route("/access-log-test", web::get().to(HttpResponse::Accepted)),
+        )
+        .await;
+        let req = TestRequest::get()
+            .uri("/access-log-test?probe=1")
+            .peer_addr("10.0.0.1:1234".parse().unwrap())
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 202);
+        logger::flush();
+
+        let path = logger::log_file_path();
+        assert!(path.starts_with(std::env::temp_dir()));
+        let logs = fs::read_to_string(path).unwrap();
+        let line = logs
+            .lines()
+            .find(|line| line.contains("/access-log-test?probe=1"))
+            .expect("request was not logged");
+        assert!(line.contains("202"), "{line}");
+        assert!(line.contains("10.0.0.1"), "{line}");
+    }
+
+    #[test]
+    fn lines_follow_the_configured_format() {
+        let req = TestRequest::get()
+            .uri("/rooms?limit=5")
+            .peer_addr("10.0.0.1:1234".parse().unwrap())
+            .insert_header((header::REFERER, "https://app.example.com/"))
+            .insert_header((header::USER_AGENT, "curl/8.0"))
+            .to_srv_request();
+        req.extensions_mut().insert(RequestId("abc".to_owned()));
+        let info = RequestInfo::new(&req);
+
+        let common = info.line(AccessLogFormat::Common, StatusCode::OK, Some(12), 3);
+        assert!(common.starts_with("10.0.0.1 - - ["), "{common}");
+        assert!(
+            common.ends_with("\"GET /rooms?limit=5 HTTP/1.1\" 200 12 3ms"),
+            "{common}"
+        );
+
+        let combined = info.line(AccessLogFormat::Combined, StatusCode::OK, None, 3);
+        assert!(
+            combined.ends_with("200 - 3ms \"https://app.example.com/\" \"curl/8.0\""),
+            "{combined}"
+        );
+
+        let json = info.line(AccessLogFormat::Json, StatusCode::NOT_FOUND, None, 3);
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "method": "GET",
+                "path": "/rooms?limit=5",
+                "status": 404,
+                "duration_ms": 3,
+                "ip": "10.0.0.1",
+                "bytes": null,
+                "request_id": "abc",
+            })
+        );
+    }
+
+    #[test]
+    fn skipped_paths_are_not_logged() {
+        let path = |uri| {
+            TestRequest::get()
+                .uri(uri)
+                .to_srv_request()
+                .path()
+                .to_owned()
+        };
+
+        let config = config::Config::from_vars(&[]);
+        assert_eq!(config.access_log_skip, ["/health", "/ready"]);
+        // Skipping matches the path, whatever the query
+        assert!(is_skipped(
+            &config.access_log_skip,
+            &path("/health?probe=1")
+        ));
+        assert!(is_skipped(&config.access_log_skip, &path("/ready")));
+        assert!(!is_skipped(
+            &config.access_log_skip,
+            &path("/health/details")
+        ));
+        assert!(!is_skipped(&config.access_log_skip, &path("/rooms")));
+
+        let config = config::Config::from_vars(&[("FERROXIDE_ACCESS_LOG_SKIP", "")]);
+        assert!(config.access_log_skip.is_empty());
+        assert!(!is_skipped(&config.access_log_skip, &path("/health")));
+    }
+}
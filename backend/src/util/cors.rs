@@ -1,27 +1,99 @@
+use std::collections::HashSet;
 use std::future::{Ready, ready};
+use std::rc::Rc;
 
 use actix_web::HttpResponse;
 use actix_web::body::{EitherBody, MessageBody};
 use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform, forward_ready};
 use actix_web::error::Error;
+use actix_web::http::header::{HeaderMap, HeaderValue};
 use actix_web::http::{Method, header};
 use futures_util::future::LocalBoxFuture;
 
+use crate::config;
+
 const METHODS: &str = "PUT, GET, OPTIONS, DELETE, POST, CONNECT, PATCH";
 const HEADERS: &str = "content-type, authorization";
-const MAX_AGE: &str = "3600";
+const MAX_AGE: u32 = 3600;
+
+#[derive(Clone)]
+struct CorsConfig {
+    /// Forward everything untouched, e.g. when a reverse proxy handles CORS.
+    disabled: bool,
+    allowed_origins: Option<HashSet<String>>,
+    credentials: bool,
+    methods: HeaderValue,
+    headers: HeaderValue,
+    max_age: HeaderValue,
+    expose_headers: Option<HeaderValue>,
+}
+
+impl CorsConfig {
+    fn is_allowed(&self, origin: &str) -> bool {
+        match &self.allowed_origins {
+            Some(allowed_origins) => allowed_origins.contains(origin),
+            None => true,
+        }
+    }
+
+    /// Returns the preflight's requested method if it is one of the allowed
+    /// methods, so it can be echoed back instead of the whole list.
+    fn requested_method(&self, requested: &HeaderValue) -> Option<HeaderValue> {
+        let requested = requested.to_str().ok()?.trim();
+        self.methods
+            .to_str()
+            .ok()?
+            .split(',')
+            .any(|method| method.trim() == requested)
+            .then(|| HeaderValue::from_str(requested).ok())
+            .flatten()
+    }
+}
 
 /// `Cors` is Actix-Web middleware that enables Cross-Origin Resource Sharing (CORS).
 ///
 /// This middleware intercepts incoming requests:
-/// - Responds to `OPTIONS` preflight requests with the configured CORS headers:
+/// - Responds to preflight requests, i.e. `OPTIONS` requests carrying
+///   `Access-Control-Request-Method`, with the configured CORS headers:
 ///   `Access-Control-Allow-Origin`, `Access-Control-Allow-Methods`,
-///   `Access-Control-Allow-Headers`, and `Access-Control-Max-Age`.
-/// - For non-OPTIONS requests, forwards to the inner service and then appends
-///   the same CORS headers to the outgoing response.
+///   `Access-Control-Allow-Headers`, and `Access-Control-Max-Age`. If the
+///   requested method is one of the allowed ones, `Access-Control-Allow-Methods`
+///   names only that method; otherwise it lists all of them, and the browser
+///   refuses the actual request.
+/// - For any other request, bare `OPTIONS` included, forwards to the inner
+///   service and then appends the same CORS headers to the outgoing response.
+///
+/// The allowed methods, headers, and max age default to the `METHODS`,
+/// `HEADERS`, and `MAX_AGE` constants in this module and can be changed with
+/// the builder methods. [`Cors::new`] also applies overrides from the
+/// `FERROXIDE_CORS_METHODS`, `FERROXIDE_CORS_HEADERS` (both comma-separated)
+/// and `FERROXIDE_CORS_MAX_AGE` (seconds) environment variables.
 ///
-/// The allowed methods, headers, and max age values are specified by the
-/// `METHODS`, `HEADERS`, and `MAX_AGE` constants in this module.
+/// Response headers that scripts should be able to read can be listed with
+/// [`Cors::expose_headers`] or `FERROXIDE_CORS_EXPOSE_HEADERS`; they are sent
+/// in `Access-Control-Expose-Headers`. The list is empty by default, in which
+/// case the header is omitted; a browser front end reading the total of a
+/// paginated list needs `FERROXIDE_CORS_EXPOSE_HEADERS=X-Total-Count`.
+///
+/// By default any `Origin` is reflected back in `Access-Control-Allow-Origin`.
+/// When the `FERROXIDE_CORS_ORIGINS` environment variable is set to a
+/// comma-separated list of origins at construction time, only those origins
+/// are echoed back; for any other origin the header is omitted, and preflight
+/// requests are rejected with `403 Forbidden` without any CORS headers.
+///
+/// Since the response depends on the request's origin, `Vary: Origin` is
+/// added to every response so shared caches don't serve one origin's headers
+/// to another.
+///
+/// Setting `FERROXIDE_CORS_CREDENTIALS=1` additionally emits
+/// `Access-Control-Allow-Credentials: true` for allowed origins. It requires
+/// `FERROXIDE_CORS_ORIGINS` to be set as well; without an allowlist, the
+/// credentials header is never sent. The allowed origin is always echoed
+/// verbatim rather than as `*`, as browsers require for credentialed requests.
+///
+/// When a reverse proxy already takes care of CORS, setting
+/// `FERROXIDE_CORS_DISABLED=1` turns the middleware into a pass-through that
+/// forwards every request, preflights included, without touching any headers.
 ///
 /// # Examples
 ///
@@ -29,12 +101,204 @@ const MAX_AGE: &str = "3600";
 /// use actix_web::{App, HttpServer};
 ///
 /// let app = App::new()
-///     .wrap(Cors);
+///     .wrap(Cors::default());
+///
+/// let app = App::new().wrap(
+///     Cors::new()
+///         .headers(["content-type", "authorization", "x-request-id"])
+///         .max_age(600),
+/// );
 /// ```
-pub struct Cors;
+pub struct Cors {
+    config: CorsConfig,
+}
+
+impl Cors {
+    /// Creates the middleware with the default settings overridden by the
+    /// `FERROXIDE_CORS_*` environment variables.
+    pub fn new() -> Self {
+        Self::from_config(&config::get().cors)
+    }
+
+    /// Creates the middleware with the default settings overridden by `config`.
+    pub fn from_config(config: &config::CorsConfig) -> Self {
+        let mut cors = Self::default();
+        cors.config.disabled = config.disabled;
+        cors.config.credentials = config.credentials;
+
+        if let Some(origins) = &config.origins {
+            cors.config.allowed_origins = Some(origins.iter().cloned().collect());
+        }
+
+        if let Some(methods) = &config.methods {
+            cors = cors.methods(methods);
+        }
+
+        if let Some(headers) = &config.headers {
+            cors = cors.headers(headers);
+        }
+
+        if let Some(expose_headers) = &config.expose_headers {
+            cors = cors.expose_headers(expose_headers);
+        }
+
+        if let Some(max_age) = config.max_age {
+            cors = cors.max_age(max_age);
+        }
+
+        cors
+    }
+
+    /// Sets the methods advertised in `Access-Control-Allow-Methods`.
+    pub fn methods<I, T>(mut self, methods: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<str>,
+    {
+        if let Some(methods) = join_header_values(methods) {
+            self.config.methods = methods;
+        }
+        self
+    }
+
+    /// Sets the headers advertised in `Access-Control-Allow-Headers`.
+    pub fn headers<I, T>(mut self, headers: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<str>,
+    {
+        if let Some(headers) = join_header_values(headers) {
+            self.config.headers = headers;
+        }
+        self
+    }
+
+    /// Sets the response headers listed in `Access-Control-Expose-Headers`.
+    ///
+    /// An empty list omits the header entirely.
+    pub fn expose_headers<I, T>(mut self, headers: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<str>,
+    {
+        self.config.expose_headers = join_header_values(headers).filter(|value| !value.is_empty());
+        self
+    }
+
+    /// Sets how long, in seconds, browsers may cache preflight responses.
+    pub fn max_age(mut self, max_age: u32) -> Self {
+        self.config.max_age = HeaderValue::from(max_age);
+        self
+    }
+}
+
+impl Default for Cors {
+    fn default() -> Self {
+        Self {
+            config: CorsConfig {
+                disabled: false,
+                allowed_origins: None,
+                credentials: false,
+                methods: HeaderValue::from_static(METHODS),
+                headers: HeaderValue::from_static(HEADERS),
+                max_age: HeaderValue::from(MAX_AGE),
+                expose_headers: None,
+            },
+        }
+    }
+}
+
+/// Joins the trimmed, non-empty `values` into a single comma-separated header value.
+fn join_header_values<I, T>(values: I) -> Option<HeaderValue>
+where
+    I: IntoIterator<Item = T>,
+    T: AsRef<str>,
+{
+    let joined = values
+        .into_iter()
+        .map(|value| value.as_ref().trim().to_owned())
+        .filter(|value| !value.is_empty())
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    match HeaderValue::from_str(&joined) {
+        Ok(value) => Some(value),
+        Err(err) => {
+            log::error!("Invalid CORS header value {joined:?}: {err}");
+            None
+        }
+    }
+}
+
+/// Adds `Origin` to the `Vary` header, preserving any values already present.
+fn vary_on_origin(headers: &mut HeaderMap) {
+    let vary = match headers
+        .get(header::VARY)
+        .and_then(|vary| vary.to_str().ok())
+    {
+        Some(vary) => {
+            let varies = vary
+                .split(',')
+                .map(str::trim)
+                .any(|value| value == "*" || value.eq_ignore_ascii_case("origin"));
+
+            if varies {
+                return;
+            }
+
+            format!("{vary}, Origin")
+        }
+        None => "Origin".to_owned(),
+    };
+
+    if let Ok(vary) = HeaderValue::from_str(&vary) {
+        headers.insert(header::VARY, vary);
+    }
+}
+
+/// Adds the CORS headers for a request from `origin` to `headers`.
+///
+/// Shared by preflight and regular responses so both always carry the same
+/// set: `Vary`, the allowed origin and credentials (only for allowed origins),
+/// methods, headers, max age and exposed headers.
+fn apply_cors_headers(headers: &mut HeaderMap, origin: &str, config: &CorsConfig) {
+    vary_on_origin(headers);
+
+    if config.is_allowed(origin) {
+        // The origin comes straight from the client, so never assume it forms
+        // a valid header value
+        match HeaderValue::from_str(origin) {
+            Ok(origin) => {
+                headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+            }
+            Err(err) => log::warn!("Ignoring malformed Origin header {origin:?}: {err}"),
+        }
+
+        // Credentials are never allowed for every origin, even if the
+        // middleware was built that way
+        if config.credentials && config.allowed_origins.is_some() {
+            headers.insert(
+                header::ACCESS_CONTROL_ALLOW_CREDENTIALS,
+                HeaderValue::from_static("true"),
+            );
+        }
+    }
+
+    headers.insert(header::ACCESS_CONTROL_ALLOW_METHODS, config.methods.clone());
+    headers.insert(header::ACCESS_CONTROL_ALLOW_HEADERS, config.headers.clone());
+    headers.insert(header::ACCESS_CONTROL_MAX_AGE, config.max_age.clone());
+
+    if let Some(expose_headers) = &config.expose_headers {
+        headers.insert(
+            header::ACCESS_CONTROL_EXPOSE_HEADERS,
+            expose_headers.clone(),
+        );
+    }
+}
 
 pub struct CorsMiddleware<S> {
     service: S,
+    config: Rc<CorsConfig>,
 }
 
 impl<S, B> Transform<S, ServiceRequest> for Cors
@@ -50,7 +314,10 @@ where
     type Future = Ready<Result<Self::Transform, Self::InitError>>;
 
     fn new_transform(&self, service: S) -> Self::Future {
-        ready(Ok(CorsMiddleware { service }))
+        ready(Ok(CorsMiddleware {
+            service,
+            config: Rc::new(self.config.clone()),
+        }))
     }
 }
 
@@ -67,6 +334,11 @@ where
     forward_ready!(service);
 
     fn call(&self, req: ServiceRequest) -> Self::Future {
+        if self.config.disabled {
+            let fut = self.service.call(req);
+            return Box::pin(async move { Ok(fut.await?.map_into_left_body()) });
+        }
+
         let origin = req
             .headers()
             .get("Origin")
@@ -75,56 +347,388 @@ where
             .trim_end_matches('/')
             .to_owned();
 
-        if req.method() == Method::OPTIONS {
-            let mut res = HttpResponse::Ok();
-
-            res.insert_header((
-                header::ACCESS_CONTROL_ALLOW_ORIGIN,
-                header::HeaderValue::from_str(&origin).unwrap(),
-            ));
+        let config = Rc::clone(&self.config);
 
-            res.insert_header((
-                header::ACCESS_CONTROL_ALLOW_METHODS,
-                header::HeaderValue::from_static(METHODS),
-            ));
+        // Only a request announcing the method it is about to make is a
+        // preflight; any other OPTIONS request belongs to the application
+        let preflight = req.method() == Method::OPTIONS
+            && req
+                .headers()
+                .contains_key(header::ACCESS_CONTROL_REQUEST_METHOD);
 
-            res.insert_header((
-                header::ACCESS_CONTROL_ALLOW_HEADERS,
-                header::HeaderValue::from_static(HEADERS),
-            ));
+        if preflight {
+            let requested_method = req
+                .headers()
+                .get(header::ACCESS_CONTROL_REQUEST_METHOD)
+                .and_then(|method| config.requested_method(method));
 
-            res.insert_header((
-                header::ACCESS_CONTROL_MAX_AGE,
-                header::HeaderValue::from_static(MAX_AGE),
-            ));
+            let res = if config.is_allowed(&origin) {
+                let mut res = HttpResponse::Ok().finish();
+                apply_cors_headers(res.headers_mut(), &origin, &config);
+                if let Some(method) = requested_method {
+                    res.headers_mut()
+                        .insert(header::ACCESS_CONTROL_ALLOW_METHODS, method);
+                }
+                res
+            } else {
+                let mut res = HttpResponse::Forbidden().finish();
+                vary_on_origin(res.headers_mut());
+                res
+            };
 
-            let res = res.finish();
             return Box::pin(async move { Ok(req.into_response(res).map_into_right_body()) });
         }
 
         let fut = self.service.call(req);
         Box::pin(async move {
             let mut res = fut.await?;
-            let headers = res.headers_mut();
+            apply_cors_headers(res.headers_mut(), &origin, &config);
 
-            headers.insert(
-                header::ACCESS_CONTROL_ALLOW_ORIGIN,
-                header::HeaderValue::from_str(&origin).unwrap(),
-            );
-            headers.insert(
-                header::ACCESS_CONTROL_ALLOW_METHODS,
-                header::HeaderValue::from_static(METHODS),
-            );
-            headers.insert(
-                header::ACCESS_CONTROL_ALLOW_HEADERS,
-                header::HeaderValue::from_static(HEADERS),
+            Ok(res.map_into_left_body())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::dev::ServiceResponse;
+    use actix_web::test::{self, TestRequest};
+    use actix_web::{App, HttpResponse, web};
+
+    use super::*;
+
+    /// Sends `req` through `cors` wrapping a handler that answers `200 OK`.
+    async fn call(cors: Cors, req: TestRequest) -> ServiceResponse<impl MessageBody> {
+        let app = test::init_service(
+            App::new()
+                .wrap(cors)
+                .route("/", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+        test::call_service(&app, req.to_request()).await
+    }
+
+    fn from_vars(vars: &[(&str, &str)]) -> Cors {
+        Cors::from_config(&config::Config::from_vars(vars).cors)
+    }
+
+    fn get(origin: &str) -> TestRequest {
+        TestRequest::get()
+            .uri("/")
+            .insert_header((header::ORIGIN, origin))
+    }
+
+    fn preflight(origin: &str) -> TestRequest {
+        TestRequest::default()
+            .method(Method::OPTIONS)
+            .uri("/")
+            .insert_header((header::ORIGIN, origin))
+            .insert_header((header::ACCESS_CONTROL_REQUEST_METHOD, "GET"))
+    }
+
+    fn allowed_origin(res: &ServiceResponse<impl MessageBody>) -> Option<&str> {
+        res.headers()
+            .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+            .map(|origin| origin.to_str().unwrap())
+    }
+
+    #[actix_web::test]
+    async fn allowlist_reflects_only_listed_origins() {
+        let origins = [(
+            "FERROXIDE_CORS_ORIGINS",
+            "https://app.example.com/, https://admin.example.com",
+        )];
+
+        let res = call(from_vars(&origins), get("https://app.example.com")).await;
+        assert_eq!(allowed_origin(&res), Some("https://app.example.com"));
+
+        let res = call(from_vars(&origins), get("https://evil.example.com")).await;
+        assert_eq!(res.status(), 200);
+        assert_eq!(allowed_origin(&res), None);
+
+        let res = call(from_vars(&[]), get("https://evil.example.com")).await;
+        assert_eq!(allowed_origin(&res), Some("https://evil.example.com"));
+    }
+
+    #[actix_web::test]
+    async fn credentials_are_only_allowed_when_enabled() {
+        let origin = "https://app.example.com";
+        let credentials = |res: &ServiceResponse<_>| {
+            res.headers()
+                .get(header::ACCESS_CONTROL_ALLOW_CREDENTIALS)
+                .cloned()
+        };
+
+        let enabled = [
+            ("FERROXIDE_CORS_ORIGINS", origin),
+            ("FERROXIDE_CORS_CREDENTIALS", "1"),
+        ];
+        for req in [get(origin), preflight(origin)] {
+            let res = call(from_vars(&enabled), req).await;
+            assert_eq!(credentials(&res).unwrap(), "true");
+            assert_eq!(allowed_origin(&res), Some(origin));
+        }
+
+        // Without an allowlist the flag is dropped, so no origin gets them
+        let unrestricted = [("FERROXIDE_CORS_CREDENTIALS", "1")];
+        for vars in [&[][..], &[enabled[0]], &unrestricted] {
+            for req in [get(origin), preflight(origin)] {
+                let res = call(from_vars(vars), req).await;
+                assert_eq!(credentials(&res), None);
+            }
+        }
+    }
+
+    #[actix_web::test]
+    async fn vary_origin_is_appended_to_response() {
+        let res = call(Cors::default(), get("https://app.example.com")).await;
+        assert_eq!(res.headers().get(header::VARY).unwrap(), "Origin");
+
+        let app = test::init_service(App::new().wrap(Cors::default()).route(
+            "/",
+            web::get().to(|| async {
+                HttpResponse::Ok()
+                    .insert_header((header::VARY, "Accept-Encoding"))
+                    .finish()
+            }),
+        ))
+        .await;
+        let res = test::call_service(&app, get("https://app.example.com").to_request()).await;
+        assert_eq!(
+            res.headers().get(header::VARY).unwrap(),
+            "Accept-Encoding, Origin"
+        );
+    }
+
+    #[actix_web::test]
+    async fn custom_methods_headers_and_max_age_are_sent() {
+        let cors = Cors::default()
+            .methods(["GET", " POST "])
+            .headers(["content-type", "x-request-id"])
+            .max_age(600);
+        let res = call(cors, get("https://app.example.com")).await;
+
+        let header = |name| res.headers().get(name).unwrap().to_str().unwrap();
+        assert_eq!(header(header::ACCESS_CONTROL_ALLOW_METHODS), "GET, POST");
+        assert_eq!(
+            header(header::ACCESS_CONTROL_ALLOW_HEADERS),
+            "content-type, x-request-id"
+        );
+        assert_eq!(header(header::ACCESS_CONTROL_MAX_AGE), "600");
+
+        let res = call(Cors::default(), get("https://app.example.com")).await;
+        assert_eq!(
+            res.headers()
+                .get(header::ACCESS_CONTROL_ALLOW_METHODS)
+                .unwrap(),
+            METHODS
+        );
+    }
+
+    #[actix_web::test]
+    async fn exposed_headers_are_listed() {
+        let app = test::init_service(
+            App::new()
+                .wrap(from_vars(&[(
+                    "FERROXIDE_CORS_EXPOSE_HEADERS",
+                    "X-Total-Count",
+                )]))
+                .route(
+                    "/",
+                    web::get().to(|| async {
+                        HttpResponse::Ok()
+                            .insert_header(("X-Total-Count", "42"))
+                            .finish()
+                    }),
+                ),
+        )
+        .await;
+        let res = test::call_service(&app, get("https://app.example.com").to_request()).await;
+        assert_eq!(res.headers().get("X-Total-Count").unwrap(), "42");
+        assert_eq!(
+            res.headers()
+                .get(header::ACCESS_CONTROL_EXPOSE_HEADERS)
+                .unwrap(),
+            "X-Total-Count"
+        );
+
+        let res = call(Cors::default(), get("https://app.example.com")).await;
+        assert!(
+            !res.headers()
+                .contains_key(header::ACCESS_CONTROL_EXPOSE_HEADERS)
+        );
+    }
+
+    #[actix_web::test]
+    async fn preflights_from_disallowed_origins_are_forbidden() {
+        let origins = [("FERROXIDE_CORS_ORIGINS", "https://app.example.com")];
+
+        let res = call(from_vars(&origins), preflight("https://app.example.com")).await;
+        assert_eq!(res.status(), 200);
+        assert_eq!(allowed_origin(&res), Some("https://app.example.com"));
+        assert_eq!(
+            res.headers()
+                .get(header::ACCESS_CONTROL_ALLOW_METHODS)
+                .unwrap(),
+            "GET"
+        );
+        assert!(res.headers().contains_key(header::ACCESS_CONTROL_MAX_AGE));
+
+        let res = call(from_vars(&origins), preflight("https://evil.example.com")).await;
+        assert_eq!(res.status(), 403);
+        assert!(
+            !res.headers()
+                .keys()
+                .any(|name| name.as_str().starts_with("access-control-"))
+        );
+    }
+
+    #[actix_web::test]
+    async fn malformed_origins_are_handled_without_panicking() {
+        // Header values can't hold most control characters, but a tab and
+        // bytes outside ASCII get through and don't make a valid string
+        for origin in [
+            &b"https://app\t.example.com"[..],
+            b"https://\xff.example.com",
+        ] {
+            let origin = HeaderValue::from_bytes(origin).unwrap();
+
+            for req in [get(""), preflight("")] {
+                let req = req.insert_header((header::ORIGIN, origin.clone()));
+                let res = call(Cors::default(), req).await;
+                assert_eq!(res.status(), 200);
+                assert!(res.headers().contains_key(header::VARY));
+            }
+        }
+    }
+
+    #[actix_web::test]
+    async fn preflight_and_regular_responses_carry_the_same_headers() {
+        let vars = [
+            ("FERROXIDE_CORS_ORIGINS", "https://app.example.com"),
+            ("FERROXIDE_CORS_CREDENTIALS", "1"),
+            ("FERROXIDE_CORS_EXPOSE_HEADERS", "X-Total-Count"),
+        ];
+        let cors_headers = |res: &ServiceResponse<_>| {
+            let mut headers = res
+                .headers()
+                .iter()
+                .filter(|(name, _)| {
+                    *name == header::VARY || name.as_str().starts_with("access-control-")
+                })
+                // A preflight narrows the allowed methods down to the requested one
+                .filter(|(name, _)| **name != header::ACCESS_CONTROL_ALLOW_METHODS)
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect::<Vec<_>>();
+            headers.sort_by(|a, b| a.0.as_str().cmp(b.0.as_str()));
+            headers
+        };
+
+        let origin = "https://app.example.com";
+        let preflight = call(from_vars(&vars), preflight(origin)).await;
+        let regular = call(from_vars(&vars), get(origin)).await;
+        assert_eq!(preflight.status(), 200);
+        assert_eq!(regular.status(), 200);
+
+        let headers = cors_headers(&preflight);
+        assert_eq!(headers.len(), 6, "{headers:?}");
+        assert_eq!(headers, cors_headers(&regular));
+        assert!(
+            preflight
+                .headers()
+                .contains_key(header::ACCESS_CONTROL_ALLOW_METHODS)
+        );
+        assert!(
+            regular
+                .headers()
+                .contains_key(header::ACCESS_CONTROL_ALLOW_METHODS)
+        );
+    }
+
+    #[actix_web::test]
+    async fn disabled_cors_adds_no_headers() {
+        let cors = || from_vars(&[("FERROXIDE_CORS_DISABLED", "1")]);
+
+        for req in [
+            get("https://app.example.com"),
+            preflight("https://app.example.com"),
+        ] {
+            let res = call(cors(), req).await;
+            assert!(
+                res.headers()
+                    .keys()
+                    .all(|name| *name != header::VARY
+                        && !name.as_str().starts_with("access-control-")),
+                "{:?}",
+                res.headers()
             );
-            headers.insert(
-                header::ACCESS_CONTROL_MAX_AGE,
-                header::HeaderValue::from_static(MAX_AGE),
+        }
+
+        // The preflight reaches the application, which has no OPTIONS route
+        let res = call(cors(), preflight("https://app.example.com")).await;
+        assert_eq!(res.status(), 404);
+    }
+
+    #[actix_web::test]
+    async fn only_real_preflights_are_short_circuited() {
+        let app = test::init_service(App::new().wrap(Cors::default()).route(
+            "/",
+            web::method(Method::OPTIONS).to(|| async { HttpResponse::NoContent().finish() }),
+        ))
+        .await;
+
+        let res = test::call_service(&app, preflight("https://app.example.com").to_request()).await;
+        assert_eq!(res.status(), 200);
+        assert_eq!(allowed_origin(&res), Some("https://app.example.com"));
+
+        let bare = TestRequest::default()
+            .method(Method::OPTIONS)
+            .uri("/")
+            .insert_header((header::ORIGIN, "https://app.example.com"));
+        let res = test::call_service(&app, bare.to_request()).await;
+        assert_eq!(res.status(), 204);
+        assert_eq!(allowed_origin(&res), Some("https://app.example.com"));
+    }
+
+    #[actix_web::test]
+    async fn preflight_max_age_follows_config() {
+        for (vars, max_age) in [
+            (&[][..], "3600"),
+            (&[("FERROXIDE_CORS_MAX_AGE", "0")][..], "0"),
+            (&[("FERROXIDE_CORS_MAX_AGE", "86400")][..], "86400"),
+        ] {
+            let res = call(from_vars(vars), preflight("https://app.example.com")).await;
+            assert_eq!(res.status(), 200);
+            assert_eq!(
+                res.headers().get(header::ACCESS_CONTROL_MAX_AGE).unwrap(),
+                max_age
             );
+        }
+    }
 
-            Ok(res.map_into_left_body())
-        })
+    #[actix_web::test]
+    async fn preflights_echo_an_allowed_requested_method() {
+        let allowed_methods = |res: &ServiceResponse<_>| {
+            res.headers()
+                .get(header::ACCESS_CONTROL_ALLOW_METHODS)
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .to_owned()
+        };
+        let delete = || {
+            preflight("https://app.example.com")
+                .insert_header((header::ACCESS_CONTROL_REQUEST_METHOD, "DELETE"))
+        };
+
+        let res = call(Cors::default(), delete()).await;
+        assert_eq!(res.status(), 200);
+        assert_eq!(allowed_methods(&res), "DELETE");
+
+        // A method outside the configured set gets the full list, which the
+        // browser then refuses the actual request against
+        let res = call(Cors::default().methods(["GET", "POST"]), delete()).await;
+        assert_eq!(res.status(), 200);
+        assert_eq!(allowed_methods(&res), "GET, POST");
     }
 }
@@ -0,0 +1,90 @@
+use std::future::{Ready, ready};
+
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform, forward_ready};
+use actix_web::error::Error;
+use actix_web::http::Method;
+
+/// Middleware answering `HEAD` requests with the matching `GET` route.
+///
+/// Routes are registered with `#[get]`, which only matches `GET`. This turns
+/// a `HEAD` request into a `GET` before it is routed, so every `GET` route
+/// also responds to `HEAD` with the same status and headers, including
+/// `Content-Length`. The body is dropped by the HTTP layer, which remembers
+/// the original method.
+///
+/// It should be the innermost middleware, so that the others still see the
+/// request as `HEAD`, e.g. the access log.
+pub struct HeadFallback;
+
+pub struct HeadFallbackMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Transform<S, ServiceRequest> for HeadFallback
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = HeadFallbackMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(HeadFallbackMiddleware { service }))
+    }
+}
+
+impl<S, B> Service<ServiceRequest> for HeadFallbackMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = S::Future;
+
+    forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        if req.method() == Method::HEAD {
+            req.head_mut().method = Method::GET;
+        }
+
+        self.service.call(req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::header;
+    use actix_web::{App, HttpResponse, web};
+
+    use super::*;
+
+    #[actix_web::test]
+    async fn head_requests_get_the_get_headers_without_a_body() {
+        // A real server, as only the HTTP layer drops the body
+        let server = actix_test::start(|| {
+            App::new().wrap(HeadFallback).route(
+                "/rooms",
+                web::get().to(|| async { HttpResponse::Ok().body("[\"general\"]") }),
+            )
+        });
+
+        let mut res = server.head("/rooms").send().await.unwrap();
+        assert_eq!(res.status(), 200);
+        assert_eq!(res.headers().get(header::CONTENT_LENGTH).unwrap(), "11");
+        assert!(res.body().await.unwrap().is_empty());
+
+        let mut res = server.get("/rooms").send().await.unwrap();
+        assert_eq!(res.headers().get(header::CONTENT_LENGTH).unwrap(), "11");
+        assert_eq!(res.body().await.unwrap(), "[\"general\"]");
+
+        let res = server.post("/rooms").send().await.unwrap();
+        assert_eq!(res.status(), 404);
+    }
+}
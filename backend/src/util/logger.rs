@@ -1,14 +1,47 @@
-use std::io::Write;
+#[cfg(feature = "dev")]
+use std::collections::VecDeque;
+use std::ffi::OsStr;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+#[cfg(feature = "dev")]
 use std::sync::Mutex;
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::{env, fmt, fs};
+use std::sync::mpsc::{self, Sender, SyncSender};
+use std::sync::{OnceLock, RwLock};
+use std::{fmt, fs, io, iter, thread};
 
-use super::get_path_to;
-use super::tz_time;
+use chrono::{DateTime, Days, NaiveDate, TimeZone};
+use chrono_tz::Tz;
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use pretty_env_logger::env_logger;
+use pretty_env_logger::env_logger::filter::{Builder as FilterBuilder, Filter};
+#[cfg(feature = "tracing")]
+use tracing_subscriber::fmt::format::Writer;
+#[cfg(feature = "tracing")]
+use tracing_subscriber::fmt::time::FormatTime;
+#[cfg(feature = "tracing")]
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+#[cfg(feature = "tracing")]
+use tracing_subscriber::layer::SubscriberExt;
+#[cfg(feature = "tracing")]
+use tracing_subscriber::{EnvFilter, Registry, reload};
 
-const MAX_LINES: usize = 8192; // 2^13 lines
-const MAX_LINES_THRESHOLD: usize = MAX_LINES + MAX_LINES / 2; // Threshold at which to truncate
+use super::{current_request_id, get_path_to, try_get_path_for};
+use super::{timezone, tz_time, tz_time_rfc3339};
+use crate::config;
+
+pub const MAX_LINES: usize = 8192; // 2^13 lines, unless FERROXIDE_LOG_MAX_LINES says otherwise
 const FILE: &str = "logs.txt";
+/// Warn and error records are additionally appended to this file in the base
+/// directory, which keeps at most `ERRORS_MAX_LINES` of them.
+const ERRORS_FILE: &str = "errors.txt";
+const ERRORS_MAX_LINES: usize = 1024; // 2^10 lines
+const MAX_TARGET_WIDTH: usize = 40;
+/// Number of formatted records kept in memory for [`recent_lines`].
+#[cfg(feature = "dev")]
+pub const RECENT_LINES: usize = 1024;
 
 struct Padded<T> {
     value: T,
@@ -21,17 +54,627 @@ impl<T: fmt::Display> fmt::Display for Padded<T> {
     }
 }
 
+/// Strategy used to keep the log file from growing without bound.
+#[derive(Clone, Copy)]
+enum Rotation {
+    /// Truncate to the last `max` lines once there are more than `max + max / 2`.
+    Lines(usize),
+    /// Truncate to roughly half of the given size once the file exceeds it.
+    Bytes(u64),
+}
+
+/// Where the writer thread appends records and how it keeps the file bounded.
+struct Sink {
+    path: PathBuf,
+    rotation: Rotation,
+    /// Number of compressed archives of rotated-out lines to keep; 0 disables archiving.
+    keep: usize,
+    /// Whether every write is followed by an fsync.
+    sync: bool,
+    /// Lines in the file, counted as they are written. Only a hint for when to
+    /// rotate; the file itself is authoritative.
+    lines: AtomicUsize,
+}
+
+/// Layout of the records appended to the log file.
+#[derive(Clone, Copy)]
+pub enum Format {
+    /// `[{level} {date}] {target} [{request id}] > {message}`, the id only
+    /// being present while a request is handled
+    Text,
+    /// One JSON object per line with `ts`, `level`, `target` and `msg` fields,
+    /// plus `request_id` while a request is handled.
+    Json,
+}
+
+/// Message consumed by the log writer thread.
+enum Message {
+    /// A formatted record to append to the log file.
+    Line(String),
+    /// Acknowledged once every previously sent record has been written.
+    Flush(SyncSender<()>),
+    /// Moves the log file aside as the archive of the given day.
+    RollOver(NaiveDate),
+}
+
+/// Error returned by [`set_filters`] for a directive that does not name a valid level.
+#[cfg(feature = "dev")]
+#[derive(Debug)]
+pub struct InvalidFilter(String);
+
+#[cfg(feature = "dev")]
+impl fmt::Display for InvalidFilter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid log filter directive: {:?}", self.0)
+    }
+}
+
+/// Wraps the `env_logger` logger so its filter can be swapped at runtime.
+///
+/// The inner logger is built to accept every record and only handles
+/// formatting; filtering is done against `FILTER`.
+struct Logger {
+    inner: env_logger::Logger,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        FILTER
+            .get()
+            .is_some_and(|filter| filter.read().unwrap().enabled(metadata))
+    }
+
+    fn log(&self, record: &Record) {
+        let matches = FILTER
+            .get()
+            .is_some_and(|filter| filter.read().unwrap().matches(record));
+
+        if matches {
+            self.inner.log(record);
+
+            // Hand the record on to the tracing subscriber, which prints it
+            // along with the spans it was emitted in
+            #[cfg(feature = "tracing")]
+            let _ = tracing_log::format_trace(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+static LOG_FILE: OnceLock<PathBuf> = OnceLock::new();
+static WRITER: OnceLock<Sender<Message>> = OnceLock::new();
+static ERRORS_WRITER: OnceLock<Sender<Message>> = OnceLock::new();
+static FILTER: OnceLock<RwLock<Filter>> = OnceLock::new();
 static MAX_MODULE_WIDTH: AtomicUsize = AtomicUsize::new(0);
-static LINE_COUNT: AtomicUsize = AtomicUsize::new(0);
+#[cfg(feature = "dev")]
+static RECENT: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+#[cfg(feature = "tracing")]
+static TRACING_FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+/// Timestamps console lines of the tracing subscriber in the configured
+/// timezone, like every other timestamp the server writes.
+#[cfg(feature = "tracing")]
+struct LocalTime;
+
+#[cfg(feature = "tracing")]
+impl FormatTime for LocalTime {
+    fn format_time(&self, w: &mut Writer<'_>) -> fmt::Result {
+        write!(w, "{}", tz_time().format("%Y-%m-%d %H:%M:%S"))
+    }
+}
 
+/// Installs the `tracing` subscriber printing console output, in place of
+/// `pretty_env_logger`.
+///
+/// It uses the same `RUST_LOG` filters, which [`set_filters`] keeps in sync,
+/// and writes to stdout or stderr like the logger would. Records of the `log`
+/// macros are forwarded to it by the logger. Events emitted through `tracing`
+/// directly, e.g. by sqlx, reach it on their own and are printed, but not
+/// written to the log file.
+#[cfg(feature = "tracing")]
+fn init_tracing(
+    config: &config::LogConfig,
+) -> Result<(), tracing::subscriber::SetGlobalDefaultError> {
+    let (filter, handle) = reload::Layer::new(EnvFilter::new(&config.filters));
+
+    let writer = if config.stdout {
+        BoxMakeWriter::new(io::stdout)
+    } else {
+        BoxMakeWriter::new(io::stderr)
+    };
+
+    let subscriber = Registry::default().with(filter).with(
+        tracing_subscriber::fmt::layer()
+            .with_timer(LocalTime)
+            .with_writer(writer),
+    );
+
+    tracing::subscriber::set_global_default(subscriber)?;
+    let _ = TRACING_FILTER.set(handle);
+    Ok(())
+}
+
+/// Appends `line` to the in-memory buffer, evicting the oldest line once it
+/// holds `RECENT_LINES`.
+#[cfg(feature = "dev")]
+fn remember(line: &str) {
+    let mut recent = RECENT.lock().unwrap_or_else(|err| err.into_inner());
+    if recent.len() == RECENT_LINES {
+        recent.pop_front();
+    }
+    recent.push_back(line.to_owned());
+}
+
+/// Returns up to `count` of the most recently logged records, oldest first,
+/// formatted as they are written to the log file.
+///
+/// The records come from an in-memory buffer of the last `RECENT_LINES`
+/// records, so no disk I/O is involved. Records logged by an earlier run of
+/// the server are not included.
+///
+/// # Examples
+///
+/// ```
+/// for line in logger::recent_lines(10) {
+///     println!("{line}");
+/// }
+/// ```
+#[cfg(feature = "dev")]
+pub fn recent_lines(count: usize) -> Vec<String> {
+    let recent = RECENT.lock().unwrap_or_else(|err| err.into_inner());
+    recent
+        .iter()
+        .skip(recent.len().saturating_sub(count))
+        .cloned()
+        .collect()
+}
+
+/// Returns the width the target column is padded to, widening it for `target`
+/// if needed.
+///
+/// Targets longer than `MAX_TARGET_WIDTH` are printed in full but don't widen
+/// the column any further, so a single deeply nested module doesn't pad every
+/// later line to its length.
 fn max_target_width(target: &str) -> usize {
-    let max_width = MAX_MODULE_WIDTH.load(Ordering::Relaxed);
-    if max_width < target.len() {
-        MAX_MODULE_WIDTH.store(target.len(), Ordering::Relaxed);
-        target.len()
+    let width = target.len().min(MAX_TARGET_WIDTH);
+    let max_width = MAX_MODULE_WIDTH.fetch_max(width, Ordering::Relaxed);
+    max_width.max(width)
+}
+
+/// Formats `record` the way it is appended to the log file.
+///
+/// Text lines use the padded `level` and `target` the console output is
+/// printed with; JSON lines carry the bare values of the record.
+fn file_line(
+    format: Format,
+    with_source: bool,
+    record: &Record,
+    level: impl fmt::Display,
+    target: impl fmt::Display,
+    request_id: Option<String>,
+) -> String {
+    let source = match (with_source, record.file(), record.line()) {
+        (true, Some(file), Some(line)) => Some(format!("{file}:{line}")),
+        (true, Some(file), None) => Some(file.to_string()),
+        _ => None,
+    };
+
+    match format {
+        Format::Text => {
+            let date = tz_time().format("%Y-%m-%d %H:%M:%S").to_string();
+            let tag = match &request_id {
+                Some(id) => format!(" [{id}]"),
+                None => String::new(),
+            };
+            let mut line = format!("[{level} {date}] {target}{tag} > {}", record.args());
+            if let Some(source) = source {
+                line.push_str(&format!(" ({source})"));
+            }
+            line
+        }
+        Format::Json => {
+            let mut line = serde_json::json!({
+                "ts": tz_time_rfc3339(),
+                "level": record.level().as_str(),
+                "target": record.target(),
+                "msg": record.args().to_string(),
+            });
+            if let Some(source) = source {
+                line["source"] = source.into();
+            }
+            if let Some(id) = request_id {
+                line["request_id"] = id.into();
+            }
+            line.to_string()
+        }
+    }
+}
+
+/// Returns the path of the file the logger appends to.
+///
+/// This is the value of the `FERROXIDE_LOG_FILE` environment variable when it
+/// is set, and `logs.txt` under the application's base directory otherwise
+/// (see [`config::LogConfig::file`]).
+///
+/// # Examples
+///
+/// ```
+/// let logs = logger::log_file_path();
+/// println!("Logging to {}", logs.display());
+/// ```
+// Only the dev routes read it back so far
+#[cfg_attr(not(feature = "dev"), allow(dead_code))]
+pub fn log_file_path() -> PathBuf {
+    match LOG_FILE.get() {
+        Some(path) => path.clone(),
+        None => log_file_path_for(config::get()),
+    }
+}
+
+/// Returns the log file `config` asks for; see [`log_file_path`].
+fn log_file_path_for(config: &config::Config) -> PathBuf {
+    match &config.log.file {
+        Some(path) => path.clone(),
+        None => base_file_path(config, FILE),
+    }
+}
+
+/// Returns the path of `name` in the base directory for `config`.
+///
+/// # Panics
+///
+/// If the base directory cannot be resolved or created, like [`get_path_to`].
+fn base_file_path(config: &config::Config, name: &str) -> PathBuf {
+    if config.home.is_none() {
+        return get_path_to(name);
+    }
+
+    match try_get_path_for(config, name) {
+        Ok(path) => path,
+        Err(err) => {
+            log::error!("Failed to create base path: {err}");
+            panic!("Failed to create base path: {err}");
+        }
+    }
+}
+
+/// Replaces the contents of the log file by writing them to a `.tmp` sibling
+/// and renaming that over it, so a crash midway never leaves a truncated or
+/// empty log behind.
+///
+/// The rename swaps in a new file, so the writer has to reopen its handle
+/// afterwards.
+fn replace_contents(path: &Path, contents: &str) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+
+    let mut file = fs::File::create(&tmp)?;
+    file.write_all(contents.as_bytes())?;
+    file.sync_all()?;
+
+    fs::rename(&tmp, path)
+}
+
+fn truncate_to_bytes(sink: &Sink, max_bytes: u64) -> io::Result<()> {
+    let contents = fs::read_to_string(&sink.path)?;
+    let mut start = contents.len().saturating_sub((max_bytes / 2) as usize);
+
+    // Skip ahead to the next line boundary so no partial line is retained
+    if start > 0 {
+        start = contents.as_bytes()[start - 1..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(contents.len(), |pos| start + pos);
+    }
+
+    archive(sink, &contents[..start]).unwrap_or_else(|err| {
+        log::error!("Failed to archive rotated log lines: {err}");
+    });
+
+    replace_contents(&sink.path, &contents[start..])
+}
+
+/// Returns the directory of the log file, `.` for a bare file name.
+fn log_dir(path: &Path) -> &Path {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    }
+}
+
+/// Returns the `<stem>-<suffix>.<ext>` file name derived from the log file's.
+fn suffixed_name(path: &Path, suffix: impl fmt::Display) -> String {
+    let stem = path
+        .file_stem()
+        .map_or("logs".into(), |stem| stem.to_string_lossy());
+
+    match path.extension() {
+        Some(ext) => format!("{stem}-{suffix}.{}", ext.to_string_lossy()),
+        None => format!("{stem}-{suffix}"),
+    }
+}
+
+/// Compresses `dropped` into a timestamped `<stem>-<ts>.<ext>.gz` file next to
+/// the log file, then deletes the oldest archives beyond `sink.keep`.
+fn archive(sink: &Sink, dropped: &str) -> io::Result<()> {
+    if sink.keep == 0 || dropped.is_empty() {
+        return Ok(());
+    }
+
+    let dir = log_dir(&sink.path);
+    let stem = sink
+        .path
+        .file_stem()
+        .map_or("logs".into(), |stem| stem.to_string_lossy());
+
+    let ts = tz_time().format("%Y%m%d-%H%M%S%3f");
+    let name = format!("{}.gz", suffixed_name(&sink.path, ts));
+
+    let mut encoder = GzEncoder::new(fs::File::create(dir.join(name))?, Compression::default());
+    encoder.write_all(dropped.as_bytes())?;
+    encoder.finish()?;
+
+    let prefix = format!("{stem}-");
+    let mut archives = fs::read_dir(dir)?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(OsStr::to_str)
+                .is_some_and(|name| name.starts_with(&prefix) && name.ends_with(".gz"))
+        })
+        .collect::<Vec<_>>();
+
+    // Timestamps sort lexicographically, so the oldest archives come first
+    archives.sort();
+    for path in &archives[..archives.len().saturating_sub(sink.keep)] {
+        fs::remove_file(path)?;
+    }
+
+    Ok(())
+}
+
+/// Truncates the log file if it grew past its limit, returning whether it was
+/// replaced and has to be reopened.
+fn rotate(sink: &Sink, file: &fs::File, written: usize) -> bool {
+    let max_lines = match sink.rotation {
+        Rotation::Lines(max_lines) => max_lines,
+        Rotation::Bytes(max_bytes) => {
+            let len = match file.metadata() {
+                Ok(metadata) => metadata.len(),
+                Err(err) => {
+                    log::error!("Failed to read log file metadata: {err}");
+                    return false;
+                }
+            };
+
+            if len <= max_bytes {
+                return false;
+            }
+
+            return match truncate_to_bytes(sink, max_bytes) {
+                Ok(()) => true,
+                Err(err) => {
+                    log::error!("Failed to truncate log file: {err}");
+                    false
+                }
+            };
+        }
+    };
+
+    let line_count = sink.lines.fetch_add(written, Ordering::Relaxed) + written;
+    if line_count <= max_lines + max_lines / 2 {
+        return false;
+    }
+
+    let lines = match fs::read_to_string(&sink.path) {
+        Ok(lines) => lines,
+        Err(err) => {
+            log::error!("Failed to read log file: {err}");
+            return false;
+        }
+    };
+
+    // The counter is only a hint; the file itself is the source of truth for
+    // how many lines to drop and how many remain afterwards
+    let lines = lines.lines().collect::<Vec<_>>();
+    let (dropped, retained) = lines.split_at(lines.len().saturating_sub(max_lines));
+
+    let mut dropped = dropped.join("\n");
+    dropped.push('\n');
+    archive(sink, &dropped).unwrap_or_else(|err| {
+        log::error!("Failed to archive rotated log lines: {err}");
+    });
+
+    let mut contents = retained.join("\n");
+    contents.push('\n');
+
+    match replace_contents(&sink.path, &contents) {
+        Ok(()) => {
+            sink.lines.store(retained.len(), Ordering::Relaxed);
+            true
+        }
+        Err(err) => {
+            log::error!("Failed to write to log file: {err}");
+            sink.lines.store(lines.len(), Ordering::Relaxed);
+            false
+        }
+    }
+}
+
+fn open_log_file(path: &Path) -> io::Result<fs::File> {
+    fs::OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// Moves the log file to `<stem>-YYYY-MM-DD.<ext>` for `date` and returns a
+/// fresh file at the original path. If that day was already archived, e.g.
+/// after a restart, the file is appended to the archive instead.
+fn roll_over_file(sink: &Sink, date: NaiveDate) -> io::Result<fs::File> {
+    let archive = log_dir(&sink.path).join(suffixed_name(&sink.path, date.format("%Y-%m-%d")));
+
+    if archive.exists() {
+        let contents = fs::read(&sink.path)?;
+        fs::OpenOptions::new()
+            .append(true)
+            .open(&archive)?
+            .write_all(&contents)?;
+        fs::remove_file(&sink.path)?;
     } else {
-        max_width
+        fs::rename(&sink.path, &archive)?;
     }
+
+    sink.lines.store(0, Ordering::Relaxed);
+    open_log_file(&sink.path)
+}
+
+/// Returns the line written at the top of the log file on startup, stamped
+/// with `now` and its UTC offset.
+fn header_line(now: DateTime<Tz>) -> String {
+    let date = now.format("%Y-%m-%d %H:%M:%S %:z");
+    format!("=============================[ {date} ]=============================")
+}
+
+/// Opens the file at `path` for appending, creating its directory first if
+/// needed, and writes the startup header to it if `header` is set.
+///
+/// # Panics
+///
+/// If the directory cannot be created or the file cannot be opened.
+fn open_sink(
+    path: PathBuf,
+    rotation: Rotation,
+    keep: usize,
+    sync: bool,
+    header: bool,
+) -> (Sink, fs::File) {
+    // Counting the header line about to be written, if any
+    let lines = fs::read_to_string(&path).map_or(0, |s| s.lines().count() + usize::from(header));
+
+    // A custom FERROXIDE_LOG_FILE may point into a directory that doesn't exist yet
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        && let Err(err) = fs::create_dir_all(parent)
+    {
+        panic!("Failed to create log directory {}: {err}", parent.display());
+    }
+
+    let mut file = match open_log_file(&path) {
+        Ok(file) => file,
+        Err(err) => panic!("Failed to open log file {}: {err}", path.display()),
+    };
+
+    if header {
+        let _ = writeln!(file, "{}", header_line(tz_time()));
+    }
+
+    let sink = Sink {
+        path,
+        rotation,
+        keep,
+        sync,
+        lines: AtomicUsize::new(lines),
+    };
+
+    (sink, file)
+}
+
+/// Spawns the background thread that owns the log file.
+///
+/// Records are drained from the returned channel in batches, appended through
+/// a buffered writer and followed by a rotation check, so threads emitting
+/// logs never touch the disk themselves.
+fn spawn_writer(sink: Sink, file: fs::File) -> Sender<Message> {
+    let (sender, receiver) = mpsc::channel();
+
+    let res = thread::Builder::new()
+        .name("logger".to_string())
+        .spawn(move || {
+            let mut file = BufWriter::new(file);
+            while let Ok(message) = receiver.recv() {
+                let mut written = 0;
+                let mut waiting = Vec::new();
+
+                for message in iter::once(message).chain(receiver.try_iter()) {
+                    match message {
+                        Message::Line(line) => {
+                            let _ = writeln!(file, "{line}");
+                            written += 1;
+                        }
+                        Message::Flush(ack) => waiting.push(ack),
+                        Message::RollOver(date) => {
+                            if let Err(err) = file.flush() {
+                                log::error!("Failed to write to log file: {err}");
+                            }
+
+                            match roll_over_file(&sink, date) {
+                                Ok(fresh) => {
+                                    file = BufWriter::new(fresh);
+                                    written = 0;
+                                }
+                                Err(err) => log::error!("Failed to roll over log file: {err}"),
+                            }
+                        }
+                    }
+                }
+
+                let mut res = file.flush();
+                if sink.sync && res.is_ok() {
+                    res = file.get_ref().sync_all();
+                }
+
+                let rotated = match res {
+                    Ok(()) => rotate(&sink, file.get_ref(), written),
+                    Err(err) => {
+                        log::error!("Failed to write to log file: {err}");
+                        false
+                    }
+                };
+
+                // The old handle still points at the file that was renamed over
+                if rotated {
+                    match open_log_file(&sink.path) {
+                        Ok(reopened) => file = BufWriter::new(reopened),
+                        Err(err) => log::error!("Failed to reopen log file: {err}"),
+                    }
+                }
+
+                for ack in waiting {
+                    let _ = ack.send(());
+                }
+            }
+        });
+
+    if let Err(err) = res {
+        panic!("Failed to spawn log writer thread: {err}");
+    }
+
+    sender
+}
+
+/// Returns the stream the console output of [`init`] is written to: stderr,
+/// or stdout with `FERROXIDE_LOG_STDOUT=1`.
+fn console_target(config: &config::LogConfig) -> env_logger::Target {
+    if cfg!(feature = "tracing") {
+        // The tracing subscriber prints the console output instead
+        env_logger::Target::Pipe(Box::new(io::sink()))
+    } else if config.stdout {
+        env_logger::Target::Stdout
+    } else {
+        env_logger::Target::Stderr
+    }
+}
+
+/// Hands `line` to the writer of the combined log file and, for warn and
+/// error records, to that of `errors.txt` as well.
+fn send_line(line: String, level: Level, combined: &Sender<Message>, errors: &Sender<Message>) {
+    if level <= Level::Warn {
+        let _ = errors.send(Message::Line(line.clone()));
+    }
+    let _ = combined.send(Message::Line(line));
 }
 
 /// Initializes the global application logger.
@@ -39,18 +682,80 @@ fn max_target_width(target: &str) -> usize {
 /// This function sets up a pretty-printed log output to stderr using
 /// `pretty_env_logger`, and concurrently appends all log records to a
 /// persistent file named `logs.txt` in the application’s base directory
-/// (via `get_path_to(FILE)`).
+/// (via `get_path_to(FILE)`). When the `FERROXIDE_LOG_FILE` environment
+/// variable is set, its value is used as the log file path verbatim instead,
+/// and any missing parent directories are created.
+///
+/// Warn and error records are additionally appended to `errors.txt` in the
+/// base directory (via `get_path_to(ERRORS_FILE)`), for alerting. It has its
+/// own writer thread and keeps only the last `ERRORS_MAX_LINES` records; the
+/// older ones are dropped rather than archived. It gets no header and is not
+/// rolled over daily, so every line in it is a record.
 ///
 /// On startup, it reads the existing file to initialize the line counter,
-/// then writes a timestamped header. Each subsequent log record is formatted
-/// with aligned level and module target fields, emitted to stderr, and
-/// appended to the file. When the total lines exceed `MAX_LINES_THRESHOLD`,
-/// the file is truncated to retain only the most recent `MAX_LINES` entries.
+/// then writes a header stamped with the current time and UTC offset of the
+/// timezone configured in the `time` module, unless `FERROXIDE_LOG_NO_HEADER=1`
+/// asks to leave it out for log parsers. Each subsequent log record is
+/// formatted with aligned level and module target fields, emitted to stderr,
+/// and handed off to a dedicated writer thread that appends it to the file,
+/// so logging never blocks the calling thread on disk I/O. When the total
+/// lines exceed one and a half times `FERROXIDE_LOG_MAX_LINES` (`MAX_LINES`
+/// by default), the file is truncated to retain only that many of the most
+/// recent entries.
+///
+/// If `FERROXIDE_LOG_MAX_BYTES` is set to a positive number, the file is
+/// rotated by size instead: after each append its length is checked, and once
+/// it exceeds the limit it is truncated to roughly half, keeping whole lines
+/// only. The two modes are mutually exclusive, with line-based rotation being
+/// the default.
+///
+/// Rather than being discarded, the lines removed by either mode are
+/// compressed into a timestamped `logs-<ts>.txt.gz` archive next to the log
+/// file. Only the newest `FERROXIDE_LOG_KEEP` archives (3 by default) are
+/// kept; setting it to 0 disables archiving.
+///
+/// With `FERROXIDE_LOG_STDOUT=1` the console output goes to stdout rather
+/// than stderr, for log shippers that only capture the former. The file sink
+/// is not affected.
+///
+/// Setting `FERROXIDE_LOG_FORMAT=json` switches the file sink (but not the
+/// stderr output) to emit one JSON object per line, which is easier to ingest
+/// into log aggregators.
+///
+/// With `FERROXIDE_LOG_SOURCE=1`, every file record additionally carries the
+/// source location it was emitted from, as a trailing `(src/foo.rs:42)`
+/// segment in text mode or a `source` field in JSON mode.
+///
+/// Records are written in batches: the writer thread appends whatever has
+/// queued up since its last write and flushes it to the OS in one go. That
+/// survives the process crashing, but not the machine. With
+/// `FERROXIDE_LOG_SYNC=1` every such write is additionally followed by an
+/// fsync, so records that were flushed are on disk even after a power loss,
+/// at the cost of an fsync per batch, which caps throughput at however many
+/// syncs the disk manages per second.
+///
+/// Records emitted while a request is handled are tagged with its id, as set
+/// by the `RequestIdentifier` middleware.
+///
+/// With the `tracing` feature, the console output is printed by a
+/// `tracing_subscriber` instead, which `log` records are bridged to through
+/// `tracing-log`. Each record is then followed by the spans it was emitted
+/// in, such as the `request` span carrying the request id. The file sink is
+/// not affected.
+///
+/// All of the above is read from [`config::get`], so this is what first
+/// parses the configuration; any invalid values found in it are logged as
+/// warnings once the logger is installed.
+///
+/// Only the first call has any effect. Later calls, or a call made after some
+/// other global logger was installed, log a warning and leave the existing
+/// logger in place, so the function is safe to use from test harnesses.
 ///
 /// # Panics
 ///
+/// - If the log file's directory cannot be created.  
 /// - If creating or opening the log file on startup fails.  
-/// - If the logger fails to initialize (`try_init()` error).  
+/// - If the writer thread cannot be spawned.  
 ///
 /// # Examples
 ///
@@ -60,32 +765,50 @@ fn max_target_width(target: &str) -> usize {
 /// log::info!("Application started");
 /// ```
 pub fn init() {
-    let mut builder = pretty_env_logger::formatted_builder();
-    let lock = Mutex::new(());
+    init_with(config::get());
+}
 
-    let log_file = get_path_to(FILE);
-    if let Ok(count) = fs::read_to_string(&log_file).map(|s| s.lines().count()) {
-        LINE_COUNT.store(count + 1, Ordering::Relaxed);
+/// Initializes the global logger as [`init`] does, but configured by `config`
+/// instead of the process-wide configuration.
+fn init_with(full_config: &config::Config) {
+    // Test harnesses may call this more than once; only the first call counts
+    if WRITER.get().is_some() {
+        log::warn!("Logger is already initialized; ignoring repeated initialization");
+        return;
     }
 
-    let mut file = match fs::OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(&log_file)
-    {
-        Ok(file) => file,
-        Err(err) => panic!("Failed to open log file: {}", err),
+    let config = &full_config.log;
+    let mut builder = pretty_env_logger::formatted_builder();
+    builder.target(console_target(config));
+
+    let log_file = log_file_path_for(full_config);
+    let _ = LOG_FILE.set(log_file.clone());
+    let rotation = match config.max_bytes {
+        Some(bytes) => Rotation::Bytes(bytes.get()),
+        None => Rotation::Lines(config.max_lines.get()),
     };
 
-    let date = tz_time().format("%Y-%m-%d %H:%M:%S").to_string();
-    let _ = writeln!(
-        file,
-        "=============================[ {} ]=============================",
-        date
+    let format = config.format;
+    let with_source = config.source;
+
+    let (sink, file) = open_sink(log_file, rotation, config.keep, config.sync, config.header);
+    let (errors_sink, errors_file) = open_sink(
+        base_file_path(full_config, ERRORS_FILE),
+        Rotation::Lines(ERRORS_MAX_LINES),
+        0,
+        config.sync,
+        false,
     );
 
-    let res = builder
-        .parse_filters(&env::var("RUST_LOG").unwrap_or_else(|_| "info".to_string()))
+    let sender = spawn_writer(sink, file);
+    let _ = WRITER.set(sender.clone());
+    let errors = spawn_writer(errors_sink, errors_file);
+    let _ = ERRORS_WRITER.set(errors.clone());
+
+    let filter = FilterBuilder::new().parse(&config.filters).build();
+
+    let logger = builder
+        .filter_level(LevelFilter::Trace)
         .format(move |buf, record| {
             let target = record.target();
             let max_width = max_target_width(target);
@@ -102,55 +825,747 @@ pub fn init() {
                 width: max_width,
             });
 
-            let res = writeln!(buf, " {} {} > {}", level, target, record.args());
+            let request_id = current_request_id();
+            let tag = match &request_id {
+                Some(id) => format!(" [{id}]"),
+                None => String::new(),
+            };
 
-            let _lock = lock.lock().unwrap();
-            let file = fs::OpenOptions::new()
-                .create(true)
-                .append(true)
-                .open(&log_file);
+            let res = writeln!(buf, " {level} {target}{tag} > {}", record.args());
 
-            let mut file = match file {
-                Ok(file) => file,
-                Err(err) => {
-                    log::error!("Failed to open log file: {}", err);
-                    return res;
-                }
-            };
+            let line = file_line(format, with_source, record, level, target, request_id);
 
-            let date = tz_time().format("%Y-%m-%d %H:%M:%S").to_string();
-            let _ = writeln!(file, "[{} {}] {} > {}", level, date, target, record.args());
+            // Only the tail endpoint of the dev routes reads these back
+            #[cfg(feature = "dev")]
+            remember(&line);
+            send_line(line, record.level(), &sender, &errors);
+            res
+        })
+        .build();
+
+    let max_level = filter.filter();
+    let _ = FILTER.set(RwLock::new(filter));
+
+    #[cfg(feature = "tracing")]
+    let tracing = init_tracing(config);
+
+    // The only possible error is another global logger having been installed
+    // first, e.g. by a test harness; keep using that one
+    if let Err(err) = log::set_boxed_logger(Box::new(Logger { inner: logger })) {
+        log::warn!("Failed to initialize logger: {err}; keeping the existing logger");
+        return;
+    }
+
+    log::set_max_level(max_level);
+
+    #[cfg(feature = "tracing")]
+    if let Err(err) = tracing {
+        log::warn!("Failed to initialize tracing subscriber: {err}; console output is disabled");
+    }
+
+    let invalid = invalid_directives(&config.filters);
+    if !invalid.is_empty() {
+        log::warn!(
+            "Ignoring invalid RUST_LOG directives: {}",
+            invalid.join(", ")
+        );
+    }
+
+    // Reported here as the configuration is parsed before any logger exists
+    for problem in &full_config.problems {
+        log::warn!("{problem}");
+    }
+}
+
+/// Initializes the global logger for the whole test binary, with its files in
+/// a temporary base directory rather than the real one.
+#[cfg(test)]
+pub fn init_for_tests() {
+    static HOME: std::sync::LazyLock<tempfile::TempDir> =
+        std::sync::LazyLock::new(|| tempfile::tempdir().unwrap());
+
+    init_with(&config::Config::from_vars(&[(
+        "FERROXIDE_HOME",
+        HOME.path().to_str().unwrap(),
+    )]));
+}
+
+/// Returns the `module=level` directives of a `RUST_LOG`-style filter string
+/// whose level doesn't parse. env_logger silently skips those, leaving the
+/// module at whatever level the other directives give it.
+///
+/// Bare words are not reported, as `RUST_LOG` reads them as module names.
+fn invalid_directives(filters: &str) -> Vec<&str> {
+    // Anything after a `/` is a message regex, not a directive
+    let directives = filters
+        .split_once('/')
+        .map_or(filters, |(directives, _)| directives);
+
+    directives
+        .split(',')
+        .map(str::trim)
+        .filter(|directive| match directive.split_once('=') {
+            Some((_, level)) => !level.is_empty() && level.parse::<LevelFilter>().is_err(),
+            None => false,
+        })
+        .collect()
+}
+
+/// Replaces the active log filters with the given `RUST_LOG`-style directives.
+///
+/// `filters` is a comma-separated list where each directive is either a bare
+/// level (`debug`) or a `module=level` pair (`sqlx=warn`). Unlike `RUST_LOG`,
+/// every directive must name its level explicitly, so a typo is rejected
+/// instead of being silently treated as a module name.
+///
+/// The change only affects records emitted after this call; nothing already
+/// written to stderr or the log file is re-filtered.
+///
+/// # Errors
+///
+/// Returns [`InvalidFilter`] with the offending directive if any level fails
+/// to parse. The active filters are left untouched in that case.
+///
+/// # Examples
+///
+/// ```
+/// logger::set_filters("info,backend=debug")?;
+/// assert!(logger::set_filters("verbose").is_err());
+/// ```
+#[cfg(feature = "dev")]
+pub fn set_filters(filters: &str) -> Result<(), InvalidFilter> {
+    for directive in filters.split(',').map(str::trim) {
+        let level = match directive.split_once('=') {
+            Some((_, level)) => level,
+            None => directive,
+        };
+
+        if level.parse::<LevelFilter>().is_err() {
+            return Err(InvalidFilter(directive.to_string()));
+        }
+    }
+
+    let filter = FilterBuilder::new().parse(filters).build();
+    log::set_max_level(filter.filter());
+
+    if let Some(current) = FILTER.get() {
+        *current.write().unwrap() = filter;
+    }
+
+    #[cfg(feature = "tracing")]
+    if let Some(handle) = TRACING_FILTER.get()
+        && let Err(err) = handle.reload(EnvFilter::new(filters))
+    {
+        log::warn!("Failed to update tracing filters: {err}");
+    }
+
+    Ok(())
+}
+
+/// Rolls the log file over at every local midnight, for as long as the
+/// returned future runs.
+///
+/// Midnight is taken in the configured timezone (see [`timezone`]), so days
+/// with a DST change are an hour shorter or longer. At each one the file is
+/// renamed to `logs-YYYY-MM-DD.txt` for the day that just ended (following the
+/// name of the configured log file) and a new one is started. These daily
+/// archives are plain text and are not deleted automatically; the size or
+/// line limits still apply within each day.
+///
+/// Meant to be spawned once in `main` when `FERROXIDE_LOG_DAILY=1`. Does
+/// nothing useful if the logger has not been initialized.
+///
+/// # Examples
+///
+/// ```
+/// actix_web::rt::spawn(logger::roll_over_daily());
+/// ```
+pub async fn roll_over_daily() {
+    loop {
+        let now = tz_time();
+        let today = now.date_naive();
+
+        // A midnight skipped by a DST change is replaced by the next hour
+        let next = today.checked_add_days(Days::new(1)).and_then(|tomorrow| {
+            let midnight = tomorrow.and_hms_opt(0, 0, 0)?;
+            timezone()
+                .from_local_datetime(&midnight)
+                .earliest()
+                .or_else(|| {
+                    let hour = tomorrow.and_hms_opt(1, 0, 0)?;
+                    timezone().from_local_datetime(&hour).earliest()
+                })
+        });
+
+        let Some(next) = next else {
+            log::error!(
+                "Failed to compute the next midnight after {now}; daily log rollover stopped"
+            );
+            return;
+        };
+
+        tokio::time::sleep((next - now).to_std().unwrap_or_default()).await;
+
+        // The timer may fire a little early; only roll over once the day changed
+        if tz_time().date_naive() > today {
+            roll_over(today);
+        }
+    }
+}
+
+/// Archives the log file as the one of `date` and starts a new one; see
+/// [`roll_over_daily`]. Records logged before this call end up in the archive.
+pub fn roll_over(date: NaiveDate) {
+    if let Some(writer) = WRITER.get() {
+        let _ = writer.send(Message::RollOver(date));
+    }
+}
+
+/// Blocks until every record logged so far has been written to the log file.
+///
+/// Records are appended by a background thread, so anything still queued when
+/// the process exits would be lost. Call this before returning from `main`.
+/// Does nothing if the logger has not been initialized.
+///
+/// # Examples
+///
+/// ```
+/// log::error!("Fatal error, shutting down");
+/// logger::flush();
+/// ```
+pub fn flush() {
+    for writer in [&WRITER, &ERRORS_WRITER]
+        .into_iter()
+        .filter_map(OnceLock::get)
+    {
+        let (ack, done) = mpsc::sync_channel(1);
+        if writer.send(Message::Flush(ack)).is_ok() {
+            let _ = done.recv();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicBool;
+
+    use super::*;
+    use crate::util::utc_time;
 
-            let line_count = LINE_COUNT.fetch_add(1, Ordering::Relaxed);
-            if line_count < MAX_LINES_THRESHOLD {
-                return res;
+    /// Opens a sink at `path` the way `init` does with the given settings.
+    fn sink(path: &Path, rotation: Rotation, keep: usize, header: bool) -> (Sink, fs::File) {
+        open_sink(path.to_path_buf(), rotation, keep, false, header)
+    }
+
+    /// Waits until the writer behind `sender` has written and rotated
+    /// everything sent before.
+    fn drain(sender: &Sender<Message>) {
+        let (ack, done) = mpsc::sync_channel(1);
+        sender.send(Message::Flush(ack)).unwrap();
+        done.recv().unwrap();
+    }
+
+    #[test]
+    fn header_is_written_to_configured_log_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.log");
+        let config = config::Config::from_vars(&[("FERROXIDE_LOG_FILE", path.to_str().unwrap())]);
+        assert_eq!(config.log.file.as_deref(), Some(path.as_path()));
+
+        let (sink, _file) = sink(&path, Rotation::Lines(MAX_LINES), 0, config.log.header);
+        assert_eq!(sink.path, path);
+
+        let contents = fs::read_to_string(&path).unwrap();
+        assert_eq!(contents.lines().count(), 1);
+        assert!(contents.starts_with("=============================[ "));
+    }
+
+    #[test]
+    fn header_is_left_out_when_suppressed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("logs.txt");
+        fs::write(&path, "previous run\n").unwrap();
+
+        let config = config::Config::from_vars(&[("FERROXIDE_LOG_NO_HEADER", "1")]);
+        assert!(!config.log.header);
+        assert!(config::Config::from_vars(&[]).log.header);
+
+        // Restarting twice adds nothing
+        for _ in 0..2 {
+            let (sink, _file) = sink(&path, Rotation::Lines(MAX_LINES), 0, config.log.header);
+            assert_eq!(sink.lines.load(Ordering::Relaxed), 1);
+        }
+        assert_eq!(fs::read_to_string(&path).unwrap(), "previous run\n");
+    }
+
+    #[test]
+    fn byte_rotation_keeps_file_under_cap() {
+        // Rotating by line count stays the default
+        assert!(config::Config::from_vars(&[]).log.max_bytes.is_none());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("logs.txt");
+        let (sink, file) = sink(&path, Rotation::Bytes(4096), 0, true);
+        let sender = spawn_writer(sink, file);
+
+        let entry = "x".repeat(300);
+        for i in 0..100 {
+            sender
+                .send(Message::Line(format!("{i:03} {entry}")))
+                .unwrap();
+            if i % 10 == 0 {
+                drain(&sender);
             }
+        }
+        drain(&sender);
 
-            let lines = fs::read_to_string(&log_file);
-            let lines = match lines {
-                Ok(lines) => lines,
-                Err(err) => {
-                    log::error!("Failed to read log file: {}", err);
-                    return res;
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(contents.len() <= 4096, "{} bytes", contents.len());
+        assert!(contents.ends_with(&format!("099 {entry}\n")));
+        assert!(contents.lines().all(|line| line.len() == entry.len() + 4));
+    }
+
+    #[test]
+    fn no_records_are_lost_under_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("logs.txt");
+        let (sink, file) = sink(&path, Rotation::Lines(MAX_LINES), 0, false);
+        let sender = spawn_writer(sink, file);
+
+        let threads = (0..4)
+            .map(|t| {
+                let sender = sender.clone();
+                thread::spawn(move || {
+                    for i in 0..1000 {
+                        sender.send(Message::Line(format!("{t} {i}"))).unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        drain(&sender);
+
+        let contents = fs::read_to_string(&path).unwrap();
+        let mut lines = contents.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 4000);
+        lines.sort_unstable();
+        lines.dedup();
+        assert_eq!(lines.len(), 4000);
+    }
+
+    #[test]
+    fn truncation_leaves_exactly_max_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("logs.txt");
+        let contents = (0..MAX_LINES * 2)
+            .map(|i| format!("{i}\n"))
+            .collect::<String>();
+        fs::write(&path, contents).unwrap();
+
+        // A counter that lags behind the file must not change what is kept
+        let (sink, file) = sink(&path, Rotation::Lines(MAX_LINES), 0, false);
+        sink.lines.store(0, Ordering::Relaxed);
+        assert!(rotate(&sink, &file, MAX_LINES * 2));
+
+        let contents = fs::read_to_string(&path).unwrap();
+        let lines = contents.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), MAX_LINES);
+        assert_eq!(sink.lines.load(Ordering::Relaxed), MAX_LINES);
+        assert_eq!(lines[0], MAX_LINES.to_string());
+        assert_eq!(lines[MAX_LINES - 1], (MAX_LINES * 2 - 1).to_string());
+    }
+
+    #[test]
+    fn concurrent_logging_neither_drops_nor_duplicates_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("logs.txt");
+        let (sink, file) = sink(&path, Rotation::Lines(MAX_LINES), 0, true);
+        let sender = spawn_writer(sink, file);
+
+        let threads = (0..8)
+            .map(|t| {
+                let sender = sender.clone();
+                thread::spawn(move || {
+                    for i in 0..5000 {
+                        sender.send(Message::Line(format!("{t} {i}"))).unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        drain(&sender);
+
+        let contents = fs::read_to_string(&path).unwrap();
+        let lines = contents.lines().collect::<Vec<_>>();
+        assert!(
+            (MAX_LINES..=MAX_LINES + MAX_LINES / 2).contains(&lines.len()),
+            "{} lines",
+            lines.len()
+        );
+        assert!(!contents.contains("====="), "header survived truncation");
+
+        // Only the oldest lines are dropped, so each thread's records form an
+        // unbroken run ending in its last one
+        let mut next = [None::<u32>; 8];
+        for line in lines {
+            let (t, i) = line.split_once(' ').unwrap();
+            let (t, i) = (t.parse::<usize>().unwrap(), i.parse::<u32>().unwrap());
+            if let Some(expected) = next[t] {
+                assert_eq!(i, expected, "thread {t}");
+            }
+            next[t] = Some(i + 1);
+        }
+        // Threads that finished early may have been truncated away entirely
+        assert!(next.iter().flatten().all(|&next| next == 5000));
+        assert!(next.iter().any(Option::is_some));
+    }
+
+    #[test]
+    fn source_location_is_appended_when_enabled() {
+        let config = config::Config::from_vars(&[("FERROXIDE_LOG_SOURCE", "1")]);
+        assert!(config.log.source);
+
+        let line = |format, with_source| {
+            file_line(
+                format,
+                with_source,
+                &Record::builder()
+                    .args(format_args!("hello"))
+                    .level(Level::Info)
+                    .target("backend")
+                    .file(Some("src/foo.rs"))
+                    .line(Some(42))
+                    .build(),
+                Level::Info,
+                "backend",
+                None,
+            )
+        };
+
+        assert!(line(Format::Text, config.log.source).ends_with("> hello (src/foo.rs:42)"));
+        assert!(!line(Format::Text, false).contains("src/foo.rs"));
+
+        let json = serde_json::from_str::<serde_json::Value>(&line(Format::Json, true)).unwrap();
+        assert_eq!(json["source"], "src/foo.rs:42");
+    }
+
+    #[test]
+    fn header_follows_configured_timezone() {
+        let config = config::Config::from_vars(&[("FERROXIDE_TZ", "America/New_York")]);
+        assert_eq!(config.timezone, Tz::America__New_York);
+
+        let now = utc_time();
+        let local = header_line(now.with_timezone(&config.timezone));
+        let default = header_line(now.with_timezone(&config::Config::from_vars(&[]).timezone));
+        assert!(
+            local.contains(" -04:00 ") || local.contains(" -05:00 "),
+            "{local}"
+        );
+        assert!(
+            default.contains(" +01:00 ") || default.contains(" +02:00 "),
+            "{default}"
+        );
+
+        let invalid = config::Config::from_vars(&[("FERROXIDE_TZ", "Mars/Olympus")]);
+        assert_eq!(invalid.timezone, Tz::Europe__Warsaw);
+        assert_eq!(invalid.problems.len(), 1);
+    }
+
+    #[test]
+    fn rotations_keep_configured_number_of_archives() {
+        let config = config::Config::from_vars(&[("FERROXIDE_LOG_KEEP", "2")]);
+        assert_eq!(config::Config::from_vars(&[]).log.keep, 3);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("logs.txt");
+        let (sink, _file) = sink(&path, Rotation::Lines(10), config.log.keep, false);
+
+        for rotation in 0..3 {
+            let contents = (0..20)
+                .map(|i| format!("{rotation} {i}\n"))
+                .collect::<String>();
+            fs::write(&path, contents).unwrap();
+            let file = open_log_file(&path).unwrap();
+            assert!(rotate(&sink, &file, 20));
+
+            // Archive names are only unique down to the millisecond
+            thread::sleep(std::time::Duration::from_millis(2));
+        }
+
+        let mut archives = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension() == Some(OsStr::new("gz")))
+            .collect::<Vec<_>>();
+        assert_eq!(archives.len(), 2);
+
+        archives.sort();
+        let mut newest = String::new();
+        flate2::read::GzDecoder::new(fs::File::open(&archives[1]).unwrap())
+            .read_to_string(&mut newest)
+            .unwrap();
+        assert!(newest.starts_with("2 0\n") && newest.ends_with("2 9\n"));
+    }
+
+    #[test]
+    fn missing_log_directories_are_created() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested/logs/app.log");
+
+        let (sink, _file) = sink(&path, Rotation::Lines(MAX_LINES), 0, true);
+        assert_eq!(sink.path, path);
+        assert!(path.parent().unwrap().is_dir());
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 1);
+    }
+
+    #[test]
+    fn synced_records_are_readable_from_another_handle() {
+        let config = config::Config::from_vars(&[("FERROXIDE_LOG_SYNC", "1")]);
+        assert!(config.log.sync);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("logs.txt");
+        let (sink, file) = open_sink(path.clone(), Rotation::Lines(MAX_LINES), 0, true, false);
+        assert!(sink.sync);
+        let sender = spawn_writer(sink, file);
+
+        let mut reader = fs::File::open(&path).unwrap();
+        for i in 0..3 {
+            sender.send(Message::Line(format!("record {i}"))).unwrap();
+            drain(&sender);
+
+            let mut contents = String::new();
+            reader.read_to_string(&mut contents).unwrap();
+            assert_eq!(contents, format!("record {i}\n"));
+        }
+    }
+
+    #[test]
+    fn repeated_initialization_does_not_panic() {
+        init_for_tests();
+        assert!(WRITER.get().is_some());
+        assert!(log_file_path().starts_with(std::env::temp_dir()));
+
+        init_for_tests();
+        log::info!("Logged after initializing twice");
+        log::logger().flush();
+    }
+
+    #[test]
+    fn console_output_follows_stdout_flag() {
+        let target = |vars: &[(&str, &str)]| console_target(&config::Config::from_vars(vars).log);
+        let stdout = [("FERROXIDE_LOG_STDOUT", "1")];
+
+        if cfg!(feature = "tracing") {
+            assert!(matches!(target(&stdout), env_logger::Target::Pipe(_)));
+        } else {
+            assert!(matches!(target(&[]), env_logger::Target::Stderr));
+            assert!(matches!(target(&stdout), env_logger::Target::Stdout));
+        }
+    }
+
+    #[test]
+    fn truncation_follows_configured_max_lines() {
+        let config = config::Config::from_vars(&[("FERROXIDE_LOG_MAX_LINES", "10")]);
+        assert_eq!(config.log.max_lines.get(), 10);
+        let config = config::Config::from_vars(&[("FERROXIDE_LOG_MAX_LINES", "0")]);
+        assert_eq!(config.log.max_lines.get(), MAX_LINES);
+        assert_eq!(config.problems.len(), 1);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("logs.txt");
+        let (sink, file) = sink(&path, Rotation::Lines(10), 0, false);
+        let sender = spawn_writer(sink, file);
+        let line_count = || fs::read_to_string(&path).unwrap().lines().count();
+
+        // The threshold is max + max / 2
+        for i in 0..15 {
+            sender.send(Message::Line(i.to_string())).unwrap();
+        }
+        drain(&sender);
+        assert_eq!(line_count(), 15);
+
+        sender.send(Message::Line("15".to_owned())).unwrap();
+        drain(&sender);
+        assert_eq!(line_count(), 10);
+        assert!(fs::read_to_string(&path).unwrap().starts_with("6\n"));
+    }
+
+    #[test]
+    fn long_targets_do_not_widen_later_lines() {
+        let long = "ferroxide::".repeat(10);
+        let width = max_target_width(&long);
+        assert_eq!(width, MAX_TARGET_WIDTH);
+        // Printed in full all the same
+        let padded = Padded {
+            value: &long,
+            width,
+        }
+        .to_string();
+        assert_eq!(padded, long);
+
+        let width = max_target_width("short");
+        assert!(width <= MAX_TARGET_WIDTH);
+        let padded = Padded {
+            value: "short",
+            width,
+        }
+        .to_string();
+        assert_eq!(padded.len(), MAX_TARGET_WIDTH);
+        assert!(padded.len() < long.len());
+    }
+
+    #[test]
+    fn truncated_file_is_never_observed_empty() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("logs.txt");
+        let (sink, file) = sink(&path, Rotation::Lines(10), 0, true);
+        let sender = spawn_writer(sink, file);
+
+        let done = Arc::new(AtomicBool::new(false));
+        let reader = {
+            let (path, done) = (path.clone(), Arc::clone(&done));
+            thread::spawn(move || {
+                let mut reads = 0;
+                while !done.load(Ordering::Relaxed) {
+                    let contents = fs::read_to_string(&path).unwrap();
+                    assert!(!contents.is_empty());
+                    reads += 1;
                 }
-            };
+                reads
+            })
+        };
 
-            let lines = lines
-                .lines()
-                .skip(line_count - MAX_LINES + 1)
-                .chain(Some(""))
-                .collect::<Vec<_>>();
+        for i in 0..1000 {
+            sender.send(Message::Line(i.to_string())).unwrap();
+            if i % 10 == 0 {
+                drain(&sender);
+            }
+        }
+        drain(&sender);
+        done.store(true, Ordering::Relaxed);
+        assert!(reader.join().unwrap() > 0);
 
-            fs::write(&log_file, lines.join("\n")).unwrap_or_else(|err| {
-                log::error!("Failed to write to log file: {}", err);
-            });
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(contents.ends_with("998\n999\n"), "{contents}");
+        assert!(contents.lines().count() <= 15);
+        assert!(!path.with_extension("txt.tmp").exists());
+    }
 
-            LINE_COUNT.store(MAX_LINES, Ordering::Relaxed);
-            res
-        })
-        .try_init();
+    #[test]
+    fn invalid_directives_are_reported_and_valid_ones_apply() {
+        let filters = "info,sqlx=verbose, actix_web=warn,backend::db=,hyper/regex=x";
+        assert_eq!(invalid_directives(filters), ["sqlx=verbose"]);
+        assert!(invalid_directives("info,sqlx=warn").is_empty());
+        // A bare word is a module name to RUST_LOG, not a level
+        assert!(invalid_directives("infoo").is_empty());
 
-    if let Err(err) = res {
-        panic!("Failed to initialize logger: {}", err);
+        let filter = FilterBuilder::new().parse(filters).build();
+        let enabled = |target: &str, level: Level| {
+            filter.enabled(&Metadata::builder().target(target).level(level).build())
+        };
+        assert!(enabled("backend", Level::Info));
+        assert!(!enabled("backend", Level::Debug));
+        assert!(enabled("actix_web", Level::Warn));
+        assert!(!enabled("actix_web", Level::Info));
+    }
+
+    #[cfg(feature = "dev")]
+    #[test]
+    fn recent_lines_are_bounded_and_newest_last() {
+        for i in 0..RECENT_LINES + 10 {
+            remember(&format!("ring {i}"));
+        }
+
+        let recent = recent_lines(RECENT_LINES * 2);
+        assert!(recent.len() <= RECENT_LINES);
+
+        // Other tests may log concurrently, so only look at this test's lines
+        let ours = recent
+            .iter()
+            .filter_map(|line| line.strip_prefix("ring ")?.parse::<usize>().ok())
+            .collect::<Vec<_>>();
+        assert!(!ours.contains(&0));
+        assert_eq!(ours.last(), Some(&(RECENT_LINES + 9)));
+        assert!(ours.is_sorted());
+
+        assert_eq!(recent_lines(0), Vec::<String>::new());
+    }
+
+    #[test]
+    fn roll_over_archives_the_previous_day() {
+        assert!(!config::Config::from_vars(&[]).log.daily);
+        assert!(
+            config::Config::from_vars(&[("FERROXIDE_LOG_DAILY", "1")])
+                .log
+                .daily
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("logs.txt");
+        let (sink, file) = sink(&path, Rotation::Lines(MAX_LINES), 0, false);
+        let sender = spawn_writer(sink, file);
+        let day = NaiveDate::from_ymd_opt(2025, 6, 1).unwrap();
+        let archive = dir.path().join("logs-2025-06-01.txt");
+
+        sender
+            .send(Message::Line("before midnight".to_owned()))
+            .unwrap();
+        sender.send(Message::RollOver(day)).unwrap();
+        sender
+            .send(Message::Line("after midnight".to_owned()))
+            .unwrap();
+        drain(&sender);
+
+        assert_eq!(fs::read_to_string(&archive).unwrap(), "before midnight\n");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "after midnight\n");
+
+        // A second rollover for the same day appends, e.g. after a restart
+        sender.send(Message::RollOver(day)).unwrap();
+        sender.send(Message::Line("next day".to_owned())).unwrap();
+        drain(&sender);
+
+        assert_eq!(
+            fs::read_to_string(&archive).unwrap(),
+            "before midnight\nafter midnight\n"
+        );
+        assert_eq!(fs::read_to_string(&path).unwrap(), "next day\n");
+    }
+
+    #[test]
+    fn only_warnings_and_errors_reach_the_error_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("logs.txt");
+        let errors_path = dir.path().join(ERRORS_FILE);
+        let writer = |path, max_lines| {
+            let (sink, file) = sink(path, Rotation::Lines(max_lines), 0, false);
+            spawn_writer(sink, file)
+        };
+        let combined = writer(&path, MAX_LINES);
+        let errors = writer(&errors_path, ERRORS_MAX_LINES);
+
+        for level in [
+            Level::Trace,
+            Level::Debug,
+            Level::Info,
+            Level::Warn,
+            Level::Error,
+        ] {
+            send_line(level.to_string(), level, &combined, &errors);
+        }
+        drain(&combined);
+        drain(&errors);
+
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "TRACE\nDEBUG\nINFO\nWARN\nERROR\n"
+        );
+        assert_eq!(fs::read_to_string(&errors_path).unwrap(), "WARN\nERROR\n");
     }
 }
@@ -0,0 +1,125 @@
+use std::future::{Ready, ready};
+use std::sync::LazyLock;
+use std::time::Instant;
+
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform, forward_ready};
+use actix_web::error::Error;
+use futures_util::future::LocalBoxFuture;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry, TextEncoder,
+};
+
+static REGISTRY: LazyLock<Registry> = LazyLock::new(Registry::new);
+
+static REQUESTS_TOTAL: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    let opts = Opts::new("http_requests_total", "Total number of HTTP requests");
+    let counter = IntCounterVec::new(opts, &["method", "route", "status"])
+        .expect("http_requests_total should be a valid metric");
+
+    REGISTRY
+        .register(Box::new(counter.clone()))
+        .expect("http_requests_total should only be registered once");
+
+    counter
+});
+
+static REQUEST_DURATION: LazyLock<HistogramVec> = LazyLock::new(|| {
+    let opts = HistogramOpts::new(
+        "http_request_duration_seconds",
+        "HTTP request latency in seconds",
+    );
+    let histogram = HistogramVec::new(opts, &["method", "route"])
+        .expect("http_request_duration_seconds should be a valid metric");
+
+    REGISTRY
+        .register(Box::new(histogram.clone()))
+        .expect("http_request_duration_seconds should only be registered once");
+
+    histogram
+});
+
+/// Renders all collected metrics in the Prometheus text exposition format.
+///
+/// # Examples
+///
+/// ```
+/// let body = render_metrics();
+/// assert!(body.contains("http_requests_total"));
+/// ```
+pub fn render_metrics() -> String {
+    // Touch the metrics so they are registered even before the first request
+    LazyLock::force(&REQUESTS_TOTAL);
+    LazyLock::force(&REQUEST_DURATION);
+
+    let mut buf = Vec::new();
+    if let Err(err) = TextEncoder::new().encode(&REGISTRY.gather(), &mut buf) {
+        log::error!("Failed to encode metrics: {err}");
+    }
+
+    String::from_utf8(buf).unwrap_or_default()
+}
+
+/// Middleware counting requests and measuring their latency per route.
+///
+/// Requests are labelled with the route pattern (e.g. `/rooms/{id}`) rather
+/// than the raw path, so the number of series stays bounded. Requests that
+/// match no route are grouped under `unmatched`.
+pub struct Metrics;
+
+pub struct MetricsMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Transform<S, ServiceRequest> for Metrics
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = MetricsMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(MetricsMiddleware { service }))
+    }
+}
+
+impl<S, B> Service<ServiceRequest> for MetricsMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<ServiceResponse<B>, Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let started = Instant::now();
+        let method = req.method().to_string();
+
+        let fut = self.service.call(req);
+        Box::pin(async move {
+            let res = fut.await?;
+            let route = res
+                .request()
+                .match_pattern()
+                .unwrap_or_else(|| "unmatched".to_owned());
+
+            REQUESTS_TOTAL
+                .with_label_values(&[method.as_str(), &route, res.status().as_str()])
+                .inc();
+
+            REQUEST_DURATION
+                .with_label_values(&[method.as_str(), &route])
+                .observe(started.elapsed().as_secs_f64());
+
+            Ok(res)
+        })
+    }
+}
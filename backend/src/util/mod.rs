@@ -1,8 +1,26 @@
+mod access_log;
 mod cors;
+mod head;
 pub mod logger;
+#[cfg(feature = "metrics")]
+mod metrics;
 mod path;
+mod rate_limit;
+mod request_id;
+mod security_headers;
+mod signal;
 mod time;
+mod tls;
 
+pub use access_log::*;
 pub use cors::*;
+pub use head::*;
+#[cfg(feature = "metrics")]
+pub use metrics::*;
 pub use path::*;
+pub use rate_limit::*;
+pub use request_id::*;
+pub use security_headers::*;
+pub use signal::*;
 pub use time::*;
+pub use tls::*;
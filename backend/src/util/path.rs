@@ -1,28 +1,119 @@
-use std::env;
-use std::fs;
-use std::path::PathBuf;
+use std::path::{Component, Path, PathBuf};
 use std::sync::OnceLock;
+use std::{env, fs, io};
+
+use crate::config;
 
 static BASE_PATH: OnceLock<PathBuf> = OnceLock::new();
 const BASE_PATH_NAME: &str = "ferroxide";
 
-fn os_specific_path() -> PathBuf {
-    let path = match env::consts::OS {
-        "windows" => format!("{}\\{BASE_PATH_NAME}", env::var("USERPROFILE").unwrap()),
-        "linux" | "macos" => format!("{}/.{BASE_PATH_NAME}", env::var("HOME").unwrap()),
-        _ => panic!("Unsupported OS"),
+fn home_var(lookup: &impl Fn(&str) -> Option<String>, name: &str) -> io::Result<String> {
+    lookup(name).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{name}: environment variable not found"),
+        )
+    })
+}
+
+/// Resolves the Linux data directory following the XDG base directory spec.
+///
+/// An existing legacy `$HOME/.ferroxide` directory takes precedence so that
+/// installations predating XDG support keep their data.
+fn xdg_data_path(lookup: &impl Fn(&str) -> Option<String>) -> io::Result<PathBuf> {
+    let home = home_var(lookup, "HOME");
+    if let Ok(home) = &home {
+        let legacy = Path::new(home).join(format!(".{BASE_PATH_NAME}"));
+        if legacy.is_dir() {
+            return Ok(legacy);
+        }
+    }
+
+    // The spec requires relative paths in XDG variables to be ignored
+    match lookup("XDG_DATA_HOME") {
+        Some(data_home) if Path::new(&data_home).is_absolute() => {
+            Ok(Path::new(&data_home).join(BASE_PATH_NAME))
+        }
+        _ => Ok(Path::new(&home?).join(".local/share").join(BASE_PATH_NAME)),
+    }
+}
+
+/// Resolves the data directory conventional for `os`, as named by
+/// [`env::consts::OS`].
+fn os_specific_path(os: &str, lookup: &impl Fn(&str) -> Option<String>) -> io::Result<PathBuf> {
+    let path = match os {
+        "windows" => format!("{}\\{BASE_PATH_NAME}", home_var(lookup, "USERPROFILE")?),
+        "linux" => return xdg_data_path(lookup),
+        // macOS, the BSDs and any other Unix share the classic dotfile layout
+        _ if cfg!(unix) => format!("{}/.{BASE_PATH_NAME}", home_var(lookup, "HOME")?),
+        os => {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("Unsupported OS: {os}"),
+            ));
+        }
+    };
+    Ok(PathBuf::from(path))
+}
+
+/// Resolves the base directory from the variables returned by `lookup`,
+/// creating it if it doesn't exist yet.
+fn resolve_base_path(lookup: impl Fn(&str) -> Option<String>) -> io::Result<PathBuf> {
+    let path = match lookup("FERROXIDE_HOME") {
+        Some(path) if !path.is_empty() => PathBuf::from(path),
+        _ => os_specific_path(env::consts::OS, &lookup)?,
     };
-    PathBuf::from(path)
+
+    if !path.exists() {
+        fs::create_dir_all(&path)?;
+    }
+
+    Ok(path)
+}
+
+/// Returns the global base directory for the application, or the error that
+/// prevented resolving or creating it.
+///
+/// This is the fallible counterpart of [`get_base_path`], resolving the
+/// directory the same way. Failures are not cached, so a later call may still
+/// succeed once the environment is fixed.
+///
+/// # Errors
+///
+/// - `NotFound` if the `HOME`/`USERPROFILE` variable the OS needs is unset
+///   (on Linux, only when `XDG_DATA_HOME` is unset as well).
+/// - `Unsupported` if the target is neither Windows nor a Unix-family OS.
+/// - Any error returned while creating the directory.
+///
+/// # Examples
+///
+/// ```
+/// match try_get_base_path() {
+///     Ok(base) => println!("Application data directory: {}", base.display()),
+///     Err(err) => eprintln!("No usable data directory: {err}"),
+/// }
+/// ```
+pub fn try_get_base_path() -> io::Result<&'static PathBuf> {
+    if let Some(path) = BASE_PATH.get() {
+        return Ok(path);
+    }
+
+    let path = resolve_base_path(|name| env::var(name).ok())?;
+    Ok(BASE_PATH.get_or_init(|| path))
 }
 
 /// Returns the global base directory for the application.
 ///
-/// On the first call, this function computes an OS-specific path:
+/// On the first call, this function uses the `FERROXIDE_HOME` environment
+/// variable verbatim if it is set, and otherwise computes an OS-specific path:
 /// - Windows: `%USERPROFILE%\ferroxide`  
-/// - Linux/macOS: `$HOME/.ferroxide`  
+/// - Linux: `$XDG_DATA_HOME/ferroxide`, or `$HOME/.local/share/ferroxide` when
+///   `XDG_DATA_HOME` is unset; an existing `$HOME/.ferroxide` is kept instead  
+/// - macOS, FreeBSD and other Unix-family systems: `$HOME/.ferroxide`  
 ///
 /// If the directory does not already exist, it will be created. Any failure
-/// during directory creation is logged at error level and causes a panic.
+/// while resolving or creating the directory is logged at error level and
+/// causes a panic; use [`try_get_base_path`] to handle it instead.
 ///
 /// Subsequent calls simply return a reference to the same initialized `PathBuf`.
 ///
@@ -32,7 +123,8 @@ fn os_specific_path() -> PathBuf {
 ///
 /// # Panics
 ///
-/// - If `env::consts::OS` is not one of `windows`, `linux`, or `macos`.
+/// - If the target is neither Windows nor a Unix-family OS.
+/// - If the `HOME`/`USERPROFILE` variable the OS needs is unset.
 /// - If creating the directory fails.
 ///
 /// # Examples
@@ -43,23 +135,68 @@ fn os_specific_path() -> PathBuf {
 /// assert!(base.exists());
 /// ```
 pub fn get_base_path() -> &'static PathBuf {
-    BASE_PATH.get_or_init(|| {
-        let path = os_specific_path();
-        if !path.exists() {
-            if let Err(err) = fs::create_dir_all(&path) {
-                log::error!("Failed to create base path: {}", err);
-                panic!("Failed to create base path: {}", err);
+    match try_get_base_path() {
+        Ok(path) => path,
+        Err(err) => {
+            log::error!("Failed to create base path: {err}");
+            panic!("Failed to create base path: {err}");
+        }
+    }
+}
+
+/// Returns the base directory for `config`, which is its `FERROXIDE_HOME`
+/// when set and the global [`try_get_base_path`] otherwise.
+///
+/// Like the global one, the directory is created if it doesn't exist yet.
+///
+/// # Errors
+///
+/// Any error returned by [`try_get_base_path`] or while creating the directory.
+///
+/// # Examples
+///
+/// ```
+/// let base = try_get_base_path_for(config::get())?;
+/// println!("Application data directory: {}", base.display());
+/// ```
+pub fn try_get_base_path_for(config: &config::Config) -> io::Result<PathBuf> {
+    match &config.home {
+        Some(home) => {
+            fs::create_dir_all(home)?;
+            Ok(home.clone())
+        }
+        None => try_get_base_path().cloned(),
+    }
+}
+
+/// Lexically normalizes `path` into a relative path without `.`/`..` components.
+///
+/// Root and prefix components are dropped, so absolute-looking inputs are
+/// treated as relative. A `..` that would climb above the start returns `None`,
+/// or is ignored when `clamp` is set.
+fn normalize(path: &Path, clamp: bool) -> Option<PathBuf> {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => normalized.push(part),
+            Component::ParentDir => {
+                if !normalized.pop() && !clamp {
+                    return None;
+                }
             }
+            Component::CurDir | Component::RootDir | Component::Prefix(_) => {}
         }
-        path
-    })
+    }
+    Some(normalized)
 }
 
 /// Constructs a path under the global base directory.
 ///
 /// This function retrieves the application's base directory via [`get_base_path`],
-/// trims any leading `'/'` characters from the provided path fragment, and then
-/// joins it to the base path.
+/// normalizes the provided path fragment, and then joins it to the base path.
+/// Leading `'/'` characters are ignored, and `..` components are resolved
+/// lexically but can never climb above the base directory; any that would are
+/// dropped. Use [`try_get_path_to`] to reject such fragments instead.
 ///
 /// # Parameters
 ///
@@ -78,9 +215,222 @@ pub fn get_base_path() -> &'static PathBuf {
 ///     config,
 ///     get_base_path().join("config/settings.toml")
 /// );
+///
+/// assert_eq!(get_path_to("../../etc/passwd"), get_base_path().join("etc/passwd"));
 /// ```
 pub fn get_path_to<T: AsRef<str>>(path: T) -> PathBuf {
-    let base_path = get_base_path();
-    let path = path.as_ref().trim_start_matches('/');
-    base_path.join(path)
+    get_path_to_p(path.as_ref())
+}
+
+/// Constructs a path under the global base directory from a [`Path`] fragment.
+///
+/// Behaves exactly like [`get_path_to`], but accepts anything path-like, so
+/// fragments that are not valid UTF-8 (e.g. file names read from disk on
+/// Unix) are joined as-is instead of going through a lossy conversion.
+///
+/// # Examples
+///
+/// ```
+/// let upload = PathBuf::from("uploads").join(file_name);
+/// let path = get_path_to_p(&upload);
+/// assert!(path.starts_with(get_base_path()));
+/// ```
+pub fn get_path_to_p<P: AsRef<Path>>(path: P) -> PathBuf {
+    join_clamped(get_base_path(), path.as_ref())
+}
+
+/// Joins `path` to `base_path`, dropping any `..` that would climb above it.
+fn join_clamped(base_path: &Path, path: &Path) -> PathBuf {
+    base_path.join(normalize(path, true).unwrap_or_default())
+}
+
+/// Constructs a path under the global base directory, rejecting fragments that
+/// would escape it.
+///
+/// Behaves like [`get_path_to`], except that a fragment whose `..` components
+/// climb above the base directory is an error rather than being clamped. The
+/// check is purely lexical; symlinks inside the base directory are not resolved.
+///
+/// # Errors
+///
+/// - `InvalidInput` if the fragment resolves outside the base directory.
+/// - Any error returned by [`try_get_base_path`].
+///
+/// # Examples
+///
+/// ```
+/// assert!(try_get_path_to("avatars/1.png").is_ok());
+/// assert!(try_get_path_to("../../etc/passwd").is_err());
+/// ```
+pub fn try_get_path_to<T: AsRef<str>>(path: T) -> io::Result<PathBuf> {
+    try_get_path_in(try_get_base_path()?, path)
+}
+
+/// Constructs a path under the base directory for `config` (see
+/// [`try_get_base_path_for`]), rejecting fragments that would escape it like
+/// [`try_get_path_to`] does.
+///
+/// # Errors
+///
+/// - `InvalidInput` if the fragment resolves outside the base directory.
+/// - Any error returned by [`try_get_base_path_for`].
+///
+/// # Examples
+///
+/// ```
+/// let database = try_get_path_for(config, &config.db_file)?;
+/// ```
+pub fn try_get_path_for<T: AsRef<str>>(config: &config::Config, path: T) -> io::Result<PathBuf> {
+    match &config.home {
+        Some(_) => try_get_path_in(&try_get_base_path_for(config)?, path),
+        None => try_get_path_to(path),
+    }
+}
+
+/// Joins `path` to `base_path`, or fails if it would escape it.
+fn try_get_path_in<T: AsRef<str>>(base_path: &Path, path: T) -> io::Result<PathBuf> {
+    let path = path.as_ref();
+
+    match normalize(Path::new(path), false) {
+        Some(normalized) => Ok(base_path.join(normalized)),
+        None => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Path {path:?} escapes the base directory"),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a lookup that only knows `vars`.
+    fn vars<'a>(vars: &'a [(&str, &str)]) -> impl Fn(&str) -> Option<String> + 'a {
+        |name| {
+            vars.iter()
+                .find(|(var, _)| *var == name)
+                .map(|(_, value)| (*value).to_owned())
+        }
+    }
+
+    #[test]
+    fn ferroxide_home_is_used_verbatim() {
+        let dir = tempfile::tempdir().unwrap();
+        let home = dir.path().join("data");
+        let home = home.to_str().unwrap();
+
+        let path = resolve_base_path(vars(&[("FERROXIDE_HOME", home), ("HOME", "/nonexistent")]));
+        assert_eq!(path.unwrap(), Path::new(home));
+        assert!(Path::new(home).is_dir());
+    }
+
+    #[test]
+    fn missing_home_is_an_error() {
+        let err = resolve_base_path(vars(&[])).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn unwritable_base_directory_is_an_error() {
+        // Running as root ignores permissions, but nothing can be created
+        // below a regular file
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let home = file.path().join("ferroxide");
+
+        assert!(resolve_base_path(vars(&[("FERROXIDE_HOME", home.to_str().unwrap())])).is_err());
+        assert!(!home.exists());
+    }
+
+    #[test]
+    fn xdg_data_home_is_followed() {
+        let home = tempfile::tempdir().unwrap();
+        let home = home.path().to_str().unwrap();
+
+        let path = xdg_data_path(&vars(&[("HOME", home), ("XDG_DATA_HOME", "/srv/data")]));
+        assert_eq!(path.unwrap(), Path::new("/srv/data/ferroxide"));
+
+        let path = xdg_data_path(&vars(&[("HOME", home)]));
+        assert_eq!(
+            path.unwrap(),
+            Path::new(home).join(".local/share/ferroxide")
+        );
+
+        // Relative values are ignored, as the spec requires
+        let path = xdg_data_path(&vars(&[("HOME", home), ("XDG_DATA_HOME", "data")]));
+        assert_eq!(
+            path.unwrap(),
+            Path::new(home).join(".local/share/ferroxide")
+        );
+
+        let path = xdg_data_path(&vars(&[("XDG_DATA_HOME", "/srv/data")]));
+        assert_eq!(path.unwrap(), Path::new("/srv/data/ferroxide"));
+    }
+
+    #[test]
+    fn legacy_directory_takes_precedence_over_xdg() {
+        let home = tempfile::tempdir().unwrap();
+        fs::create_dir(home.path().join(".ferroxide")).unwrap();
+        let home = home.path().to_str().unwrap();
+
+        let path = xdg_data_path(&vars(&[("HOME", home), ("XDG_DATA_HOME", "/srv/data")]));
+        assert_eq!(path.unwrap(), Path::new(home).join(".ferroxide"));
+    }
+
+    #[test]
+    fn fragments_cannot_escape_the_base_directory() {
+        let normalize = |path: &str, clamp| normalize(Path::new(path), clamp);
+
+        assert_eq!(
+            normalize("logs/./a/../b.txt", false).unwrap(),
+            Path::new("logs/b.txt")
+        );
+        assert_eq!(
+            normalize("/etc/passwd", false).unwrap(),
+            Path::new("etc/passwd")
+        );
+        assert_eq!(
+            normalize("//etc/../passwd", false).unwrap(),
+            Path::new("passwd")
+        );
+
+        assert_eq!(normalize("../../etc/passwd", false), None);
+        assert_eq!(normalize("logs/../../etc", false), None);
+        assert_eq!(
+            normalize("../../etc/passwd", true).unwrap(),
+            Path::new("etc/passwd")
+        );
+        assert_eq!(normalize("/..", true).unwrap(), Path::new(""));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn other_unix_targets_use_dotfile_directory() {
+        let lookup = vars(&[("HOME", "/home/beastie")]);
+        for os in ["freebsd", "openbsd", "macos"] {
+            let path = os_specific_path(os, &lookup).unwrap();
+            assert_eq!(path, Path::new("/home/beastie/.ferroxide"));
+        }
+
+        let err = os_specific_path("freebsd", &vars(&[])).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_fragments_are_joined_as_is() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let name = OsStr::from_bytes(b"upload-\xff.bin");
+        assert!(name.to_str().is_none());
+
+        let base = Path::new("/srv/ferroxide");
+        let fragment = Path::new("/uploads").join(name);
+        let path = join_clamped(base, &fragment);
+        assert_eq!(path, base.join("uploads").join(name));
+        assert_eq!(path.file_name(), Some(name));
+
+        let fragment = Path::new("../..").join(name);
+        assert_eq!(join_clamped(base, &fragment), base.join(name));
+    }
 }
@@ -0,0 +1,202 @@
+use std::collections::{HashMap, VecDeque};
+use std::future::{Ready, ready};
+use std::net::IpAddr;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use actix_web::HttpResponse;
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform, forward_ready};
+use actix_web::error::Error;
+use actix_web::http::header;
+use futures_util::future::LocalBoxFuture;
+
+use crate::config;
+
+/// Number of tracked clients above which idle ones are pruned from the map.
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// Timestamps of the recent requests of every client, shared by all workers.
+type History = Arc<Mutex<HashMap<IpAddr, VecDeque<Instant>>>>;
+
+/// Middleware limiting how many requests a single client IP may make within
+/// a sliding time window.
+///
+/// [`RateLimiter::new`] reads the limit from `FERROXIDE_RATE_LIMIT` (requests
+/// per window) and the window length from `FERROXIDE_RATE_LIMIT_WINDOW`
+/// (seconds, 60 by default). Without a limit every request is let through.
+///
+/// Clients over the limit get `429 Too Many Requests` with a `Retry-After`
+/// header telling them how many seconds to wait. The client is identified by
+/// the peer address of the connection, so behind a reverse proxy all requests
+/// appear to come from the proxy.
+///
+/// The limiter must be created once, outside the `HttpServer` factory, and
+/// cloned into each `App`, so that all workers share the same counters.
+///
+/// # Examples
+///
+/// ```rust
+/// let limiter = RateLimiter::new();
+/// HttpServer::new(move || App::new().wrap(limiter.clone()));
+/// ```
+#[derive(Clone)]
+pub struct RateLimiter {
+    limit: Option<usize>,
+    window: Duration,
+    history: History,
+}
+
+impl RateLimiter {
+    /// Creates the limiter configured from the `FERROXIDE_RATE_LIMIT*`
+    /// environment variables.
+    pub fn new() -> Self {
+        Self::from_config(config::get())
+    }
+
+    /// Creates the limiter from the `rate_limit*` fields of `config`.
+    pub fn from_config(config: &config::Config) -> Self {
+        Self {
+            limit: config.rate_limit.map(NonZeroUsize::get),
+            window: Duration::from_secs(config.rate_limit_window),
+            history: Arc::default(),
+        }
+    }
+
+    /// Records a request from `ip` made at `now`.
+    ///
+    /// Returns `None` if the request is allowed, or how long the client has to
+    /// wait before its next request would be.
+    fn check(&self, limit: usize, ip: IpAddr, now: Instant) -> Option<Duration> {
+        let mut history = self.history.lock().unwrap_or_else(|err| err.into_inner());
+
+        if history.len() > PRUNE_THRESHOLD {
+            history.retain(|_, requests| {
+                requests
+                    .back()
+                    .is_some_and(|last| now.duration_since(*last) < self.window)
+            });
+        }
+
+        let requests = history.entry(ip).or_default();
+        while requests
+            .front()
+            .is_some_and(|first| now.duration_since(*first) >= self.window)
+        {
+            requests.pop_front();
+        }
+
+        if requests.len() >= limit {
+            let oldest = requests.front().copied().unwrap_or(now);
+            return Some(self.window.saturating_sub(now.duration_since(oldest)));
+        }
+
+        requests.push_back(now);
+        None
+    }
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct RateLimiterMiddleware<S> {
+    service: S,
+    limiter: RateLimiter,
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimiter
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = RateLimiterMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimiterMiddleware {
+            service,
+            limiter: self.clone(),
+        }))
+    }
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimiterMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<ServiceResponse<EitherBody<B>>, Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let retry_after = match (self.limiter.limit, req.peer_addr()) {
+            (Some(limit), Some(peer)) => self.limiter.check(limit, peer.ip(), Instant::now()),
+            _ => None,
+        };
+
+        if let Some(retry_after) = retry_after {
+            // Round up so that clients never retry too early
+            let secs = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+            let res = HttpResponse::TooManyRequests()
+                .insert_header((header::RETRY_AFTER, secs.max(1)))
+                .finish();
+
+            return Box::pin(async move { Ok(req.into_response(res).map_into_right_body()) });
+        }
+
+        let fut = self.service.call(req);
+        Box::pin(async move { Ok(fut.await?.map_into_left_body()) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::{self, TestRequest};
+    use actix_web::{App, web};
+
+    use super::*;
+
+    #[actix_web::test]
+    async fn clients_over_the_limit_are_refused_per_ip() {
+        let config = config::Config::from_vars(&[("FERROXIDE_RATE_LIMIT", "2")]);
+        let app = test::init_service(
+            App::new()
+                .wrap(RateLimiter::from_config(&config))
+                .route("/", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+        let get = |ip: &str| {
+            TestRequest::get()
+                .peer_addr(format!("{ip}:1234").parse().unwrap())
+                .to_request()
+        };
+
+        for _ in 0..2 {
+            assert_eq!(
+                test::call_service(&app, get("10.0.0.1")).await.status(),
+                200
+            );
+        }
+
+        let res = test::call_service(&app, get("10.0.0.1")).await;
+        assert_eq!(res.status(), 429);
+        assert_eq!(res.headers().get(header::RETRY_AFTER).unwrap(), "60");
+
+        assert_eq!(
+            test::call_service(&app, get("10.0.0.2")).await.status(),
+            200
+        );
+    }
+}
@@ -0,0 +1,213 @@
+use std::future::{Ready, ready};
+
+use actix_web::HttpMessage;
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform, forward_ready};
+use actix_web::error::Error;
+use actix_web::http::header::{HeaderName, HeaderValue};
+use futures_util::future::LocalBoxFuture;
+#[cfg(feature = "tracing")]
+use tracing::Instrument;
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Longest client supplied id that is accepted; longer ones are replaced.
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+tokio::task_local! {
+    static CURRENT_REQUEST_ID: String;
+}
+
+/// The id of the request being handled, stored in the request extensions by
+/// the [`RequestIdentifier`] middleware.
+#[derive(Clone, Debug)]
+pub struct RequestId(pub String);
+
+/// Returns the id of the request currently being handled on this task, if any.
+///
+/// This is what lets log records emitted from inside a handler carry the id
+/// without it being passed around explicitly.
+///
+/// # Examples
+///
+/// ```
+/// if let Some(id) = current_request_id() {
+///     println!("handling request {id}");
+/// }
+/// ```
+pub fn current_request_id() -> Option<String> {
+    CURRENT_REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+/// Middleware tagging every request with a unique id.
+///
+/// The id is taken from the incoming `X-Request-Id` header if present and
+/// sane, and generated as a random UUID otherwise. It is stored in the request
+/// extensions as [`RequestId`], exposed to the logger through
+/// [`current_request_id`] and echoed back in the response's `X-Request-Id`
+/// header.
+///
+/// With the `tracing` feature, handling the request is additionally wrapped
+/// in a `request` span with `id`, `method` and `path` fields, so nested
+/// spans and events can be correlated with it.
+pub struct RequestIdentifier;
+
+pub struct RequestIdentifierMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RequestIdentifier
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = RequestIdentifierMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestIdentifierMiddleware { service }))
+    }
+}
+
+impl<S, B> Service<ServiceRequest> for RequestIdentifierMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<ServiceResponse<B>, Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let id = req
+            .headers()
+            .get(&REQUEST_ID_HEADER)
+            .and_then(|id| id.to_str().ok())
+            .filter(|id| !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN)
+            .map(str::to_owned)
+            .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+        req.extensions_mut().insert(RequestId(id.clone()));
+
+        let header = HeaderValue::from_str(&id);
+
+        #[cfg(feature = "tracing")]
+        let span = tracing::info_span!("request", %id, method = %req.method(), path = req.path());
+
+        let fut = CURRENT_REQUEST_ID.scope(id, self.service.call(req));
+
+        #[cfg(feature = "tracing")]
+        let fut = fut.instrument(span);
+
+        Box::pin(async move {
+            let mut res = fut.await?;
+            if let Ok(header) = header {
+                res.headers_mut().insert(REQUEST_ID_HEADER, header);
+            }
+
+            Ok(res)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::{self, TestRequest};
+    use actix_web::{App, HttpResponse, web};
+
+    use super::*;
+
+    #[actix_web::test]
+    async fn request_id_is_echoed_and_visible_to_handlers() {
+        let app = test::init_service(App::new().wrap(RequestIdentifier).route(
+            "/",
+            web::get().to(|| async { HttpResponse::Ok().body(current_request_id().unwrap()) }),
+        ))
+        .await;
+
+        let res = test::call_service(&app, TestRequest::get().uri("/").to_request()).await;
+        let id = res.headers().get(&REQUEST_ID_HEADER).unwrap().clone();
+        assert!(Uuid::parse_str(id.to_str().unwrap()).is_ok());
+        assert_eq!(test::read_body(res).await, id.as_bytes());
+
+        let req = TestRequest::get()
+            .uri("/")
+            .insert_header((REQUEST_ID_HEADER, "client-supplied-42"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(
+            res.headers().get(&REQUEST_ID_HEADER).unwrap(),
+            "client-supplied-42"
+        );
+        assert_eq!(test::read_body(res).await, "client-supplied-42");
+
+        assert_eq!(current_request_id(), None);
+    }
+
+    #[cfg(feature = "tracing")]
+    #[actix_web::test]
+    async fn log_records_carry_the_request_span() {
+        use std::io;
+        use std::sync::{Arc, Mutex};
+
+        #[derive(Clone, Default)]
+        struct Captured(Arc<Mutex<Vec<u8>>>);
+
+        impl io::Write for Captured {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.lock().unwrap().write(buf)
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let captured = Captured::default();
+        let subscriber = tracing_subscriber::fmt()
+            .with_ansi(false)
+            .with_writer({
+                let captured = captured.clone();
+                move || captured.clone()
+            })
+            .finish();
+        // The test runtime is single-threaded, so the handler runs on this thread
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let app = test::init_service(App::new().wrap(RequestIdentifier).route(
+            "/rooms",
+            web::get().to(|| async {
+                // What the logger hands on for records of the `log` macros
+                let _ = tracing_log::format_trace(
+                    &log::Record::builder()
+                        .args(format_args!("listing rooms"))
+                        .level(log::Level::Info)
+                        .target("backend::routes")
+                        .build(),
+                );
+                HttpResponse::Ok().finish()
+            }),
+        ))
+        .await;
+
+        let req = TestRequest::get()
+            .uri("/rooms")
+            .insert_header((REQUEST_ID_HEADER, "traced-7"))
+            .to_request();
+        test::call_service(&app, req).await;
+
+        let output = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        assert!(
+            output.contains("request{id=traced-7 method=GET path=\"/rooms\"}"),
+            "{output}"
+        );
+        assert!(output.contains("listing rooms"), "{output}");
+    }
+}
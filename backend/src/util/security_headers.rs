@@ -0,0 +1,213 @@
+use std::future::{Ready, ready};
+use std::rc::Rc;
+
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform, forward_ready};
+use actix_web::error::Error;
+use actix_web::http::header::{self, HeaderName, HeaderValue};
+use futures_util::future::LocalBoxFuture;
+
+use crate::config;
+
+const FRAME_OPTIONS: &str = "DENY";
+const REFERRER_POLICY: &str = "no-referrer";
+
+/// Middleware adding standard security headers to every response.
+///
+/// By default responses carry:
+///
+/// - `X-Content-Type-Options: nosniff`, so browsers never guess a content type
+///   other than the declared one;
+/// - `X-Frame-Options: DENY`, so no page can embed ours in a frame, overridden
+///   with `FERROXIDE_FRAME_OPTIONS` (e.g. `SAMEORIGIN`);
+/// - `Referrer-Policy: no-referrer`, overridden with
+///   `FERROXIDE_REFERRER_POLICY`.
+///
+/// A `Content-Security-Policy` is only sent when `FERROXIDE_CSP` is set, as a
+/// useful policy depends on what the frontend loads. Setting any of the
+/// variables to an empty value omits that header, and values that are not
+/// valid header values are logged and ignored.
+///
+/// Headers a handler already set are left alone, so individual routes can
+/// relax the policy.
+///
+/// # Examples
+///
+/// ```rust
+/// let app = App::new()
+///     .wrap(SecurityHeaders::new());
+/// ```
+pub struct SecurityHeaders {
+    headers: Rc<Vec<(HeaderName, HeaderValue)>>,
+}
+
+impl SecurityHeaders {
+    /// Creates the middleware with the default headers overridden by the
+    /// `FERROXIDE_FRAME_OPTIONS`, `FERROXIDE_REFERRER_POLICY` and
+    /// `FERROXIDE_CSP` environment variables.
+    pub fn new() -> Self {
+        Self::from_config(&config::get().security_headers)
+    }
+
+    /// Creates the middleware with the default headers overridden by `config`.
+    pub fn from_config(config: &config::SecurityHeadersConfig) -> Self {
+        let headers = [
+            (header::X_CONTENT_TYPE_OPTIONS, Some("nosniff")),
+            (
+                header::X_FRAME_OPTIONS,
+                Some(config.frame_options.as_deref().unwrap_or(FRAME_OPTIONS)),
+            ),
+            (
+                header::REFERRER_POLICY,
+                Some(config.referrer_policy.as_deref().unwrap_or(REFERRER_POLICY)),
+            ),
+            (
+                header::CONTENT_SECURITY_POLICY,
+                config.content_security_policy.as_deref(),
+            ),
+        ];
+
+        let headers = headers
+            .into_iter()
+            .filter_map(|(name, value)| {
+                let value = value.map(str::trim).filter(|value| !value.is_empty())?;
+                match HeaderValue::from_str(value) {
+                    Ok(value) => Some((name, value)),
+                    Err(err) => {
+                        log::error!("Invalid {name} header value {value:?}: {err}");
+                        None
+                    }
+                }
+            })
+            .collect();
+
+        Self {
+            headers: Rc::new(headers),
+        }
+    }
+}
+
+pub struct SecurityHeadersMiddleware<S> {
+    service: S,
+    headers: Rc<Vec<(HeaderName, HeaderValue)>>,
+}
+
+impl<S, B> Transform<S, ServiceRequest> for SecurityHeaders
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = SecurityHeadersMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(SecurityHeadersMiddleware {
+            service,
+            headers: Rc::clone(&self.headers),
+        }))
+    }
+}
+
+impl<S, B> Service<ServiceRequest> for SecurityHeadersMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<ServiceResponse<B>, Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let headers = Rc::clone(&self.headers);
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let mut res = fut.await?;
+            for (name, value) in headers.iter() {
+                if !res.headers().contains_key(name) {
+                    res.headers_mut().insert(name.clone(), value.clone());
+                }
+            }
+
+            Ok(res)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::{self, TestRequest};
+    use actix_web::{App, HttpResponse, web};
+
+    use super::*;
+
+    /// Sends a `GET` for `uri` through the middleware configured with `vars`
+    /// and returns the response headers.
+    async fn get(vars: &[(&str, &str)], uri: &str) -> header::HeaderMap {
+        let config = config::Config::from_vars(vars);
+        let app = test::init_service(
+            App::new()
+                .wrap(SecurityHeaders::from_config(&config.security_headers))
+                .route("/", web::get().to(HttpResponse::Ok))
+                .route(
+                    "/embeddable",
+                    web::get().to(|| async {
+                        HttpResponse::Ok()
+                            .insert_header((header::X_FRAME_OPTIONS, "SAMEORIGIN"))
+                            .finish()
+                    }),
+                ),
+        )
+        .await;
+        let res = test::call_service(&app, TestRequest::get().uri(uri).to_request()).await;
+        res.headers().clone()
+    }
+
+    #[actix_web::test]
+    async fn default_headers_are_added() {
+        let headers = get(&[], "/").await;
+        assert_eq!(
+            headers.get(header::X_CONTENT_TYPE_OPTIONS).unwrap(),
+            "nosniff"
+        );
+        assert_eq!(headers.get(header::X_FRAME_OPTIONS).unwrap(), FRAME_OPTIONS);
+        assert_eq!(
+            headers.get(header::REFERRER_POLICY).unwrap(),
+            REFERRER_POLICY
+        );
+        assert!(!headers.contains_key(header::CONTENT_SECURITY_POLICY));
+
+        // Handlers can relax the policy for their own responses
+        let headers = get(&[], "/embeddable").await;
+        assert_eq!(headers.get(header::X_FRAME_OPTIONS).unwrap(), "SAMEORIGIN");
+    }
+
+    #[actix_web::test]
+    async fn headers_follow_config() {
+        let headers = get(
+            &[
+                ("FERROXIDE_FRAME_OPTIONS", ""),
+                ("FERROXIDE_REFERRER_POLICY", "same-origin"),
+                ("FERROXIDE_CSP", "default-src 'self'"),
+            ],
+            "/",
+        )
+        .await;
+        assert!(!headers.contains_key(header::X_FRAME_OPTIONS));
+        assert_eq!(headers.get(header::REFERRER_POLICY).unwrap(), "same-origin");
+        assert_eq!(
+            headers.get(header::CONTENT_SECURITY_POLICY).unwrap(),
+            "default-src 'self'"
+        );
+        assert_eq!(
+            headers.get(header::X_CONTENT_TYPE_OPTIONS).unwrap(),
+            "nosniff"
+        );
+    }
+}
@@ -0,0 +1,34 @@
+use std::io;
+
+/// Waits until the process is asked to shut down and returns the signal's name.
+///
+/// On Unix this resolves on either `SIGINT` or `SIGTERM` (as sent by
+/// `docker stop` or Kubernetes); elsewhere only Ctrl-C is supported.
+///
+/// # Errors
+///
+/// Returns an error if the signal handlers cannot be registered.
+///
+/// # Examples
+///
+/// ```
+/// let signal = shutdown_signal().await?;
+/// log::info!("{signal} received; shutting down");
+/// ```
+pub async fn shutdown_signal() -> io::Result<&'static str> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        let mut terminate = signal(SignalKind::terminate())?;
+        tokio::select! {
+            res = tokio::signal::ctrl_c() => res.map(|()| "SIGINT"),
+            _ = terminate.recv() => Ok("SIGTERM"),
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        tokio::signal::ctrl_c().await.map(|()| "Ctrl-C")
+    }
+}
@@ -1,17 +1,52 @@
-use chrono::Utc;
+use std::sync::OnceLock;
+use std::time::Instant;
+
+use chrono::{DateTime, TimeZone, Utc};
 use chrono_tz::Tz;
 
-const TIMEZONE: Tz = Tz::Europe__Warsaw;
+use crate::config;
+
+static STARTED: OnceLock<Instant> = OnceLock::new();
+
+/// Returns the timezone used for local timestamps.
+///
+/// This is the IANA timezone named by `FERROXIDE_TZ`, as parsed once into
+/// [`config::Config::timezone`], falling back to `Europe/Warsaw` when the
+/// variable is unset or invalid. Invalid values are reported by the logger
+/// on startup.
+///
+/// # Examples
+///
+/// ```
+/// let tz = timezone();
+/// println!("Local timestamps use {}", tz.name());
+/// ```
+pub fn timezone() -> Tz {
+    config::get().timezone
+}
+
+/// Returns the seconds from the Unix epoch to `datetime`, or 0 if it lies
+/// before the epoch.
+fn saturating_s<T: TimeZone>(datetime: &DateTime<T>) -> u64 {
+    u64::try_from(datetime.timestamp()).unwrap_or(0)
+}
+
+/// Returns the milliseconds from the Unix epoch to `datetime`, or 0 if it
+/// lies before the epoch.
+fn saturating_ms<T: TimeZone>(datetime: &DateTime<T>) -> u64 {
+    u64::try_from(datetime.timestamp_millis()).unwrap_or(0)
+}
 
 /// Returns the current timestamp in seconds for the configured timezone.
 ///
 /// This function obtains the current UTC time, converts it to the
-/// `Europe/Warsaw` timezone, and then returns the Unix timestamp
-/// (seconds since the Unix epoch) as a `u64`.
+/// timezone returned by [`timezone`], and then returns the Unix timestamp
+/// (seconds since the Unix epoch) as a `u64`. If the system clock is set
+/// before 1970, the result saturates to 0 instead of wrapping around.
 ///
 /// # Returns
 ///
-/// A `u64` representing the current time in seconds in the `Europe/Warsaw` timezone.
+/// A `u64` representing the current time in seconds in the configured timezone.
 ///
 /// # Examples
 ///
@@ -20,19 +55,20 @@ const TIMEZONE: Tz = Tz::Europe__Warsaw;
 /// assert!(now_secs > 0);
 /// ```
 pub fn tz_time_s() -> u64 {
-    let utc = Utc::now().with_timezone(&TIMEZONE);
-    utc.timestamp() as u64
+    let utc = Utc::now().with_timezone(&timezone());
+    saturating_s(&utc)
 }
 
 /// Returns the current timestamp in milliseconds for the configured timezone.
 ///
 /// This function obtains the current UTC time, converts it to the
-/// `Europe/Warsaw` timezone, and then returns the Unix timestamp
-/// (milliseconds since the Unix epoch) as a `u64`.
+/// timezone returned by [`timezone`], and then returns the Unix timestamp
+/// (milliseconds since the Unix epoch) as a `u64`. If the system clock is set
+/// before 1970, the result saturates to 0 instead of wrapping around.
 ///
 /// # Returns
 ///
-/// A `u64` representing the current time in milliseconds in the `Europe/Warsaw` timezone.
+/// A `u64` representing the current time in milliseconds in the configured timezone.
 ///
 /// # Examples
 ///
@@ -41,19 +77,19 @@ pub fn tz_time_s() -> u64 {
 /// assert!(now_millis > 0);
 /// ```
 pub fn tz_time_ms() -> u64 {
-    let utc = Utc::now().with_timezone(&TIMEZONE);
-    utc.timestamp_millis() as u64
+    let utc = Utc::now().with_timezone(&timezone());
+    saturating_ms(&utc)
 }
 
 /// Returns the current date and time in the configured timezone.
 ///
 /// This function obtains the current UTC time and converts it to the
-/// `Europe/Warsaw` timezone, returning a `chrono::DateTime<Tz>` instance
-/// for further manipulation or formatting.
+/// timezone returned by [`timezone`], returning a `chrono::DateTime<Tz>`
+/// instance for further manipulation or formatting.
 ///
 /// # Returns
 ///
-/// A `chrono::DateTime<Tz>` representing the current local time in the `Europe/Warsaw` timezone.
+/// A `chrono::DateTime<Tz>` representing the current local time in the configured timezone.
 ///
 /// # Examples
 ///
@@ -62,5 +98,334 @@ pub fn tz_time_ms() -> u64 {
 /// println!("Current local time: {}", local_dt);
 /// ```
 pub fn tz_time() -> chrono::DateTime<Tz> {
-    Utc::now().with_timezone(&TIMEZONE)
+    utc_time().with_timezone(&timezone())
+}
+
+/// Returns the current date and time in UTC.
+///
+/// Unlike [`tz_time`], the result does not depend on the configured timezone,
+/// which makes it the right choice for values that are compared or stored,
+/// such as database timestamps or token expiry times.
+///
+/// # Returns
+///
+/// A `chrono::DateTime<Utc>` representing the current time in UTC.
+///
+/// # Examples
+///
+/// ```
+/// let now = utc_time();
+/// println!("Current UTC time: {}", now);
+/// ```
+pub fn utc_time() -> chrono::DateTime<Utc> {
+    Utc::now()
+}
+
+/// Returns the current timestamp in seconds since the Unix epoch.
+///
+/// A Unix timestamp is the same in every timezone, so this always agrees with
+/// [`tz_time_s`]; it merely spares callers that want UTC the detour through
+/// the configured timezone. If the system clock is set before 1970, the
+/// result saturates to 0 instead of wrapping around.
+///
+/// # Returns
+///
+/// A `u64` representing the current time in seconds since the Unix epoch.
+///
+/// # Examples
+///
+/// ```
+/// let now_secs = utc_time_s();
+/// assert!(now_secs > 0);
+/// ```
+#[allow(dead_code)] // Public API for e.g. JWT `exp` claims; nothing in the server calls it yet
+pub fn utc_time_s() -> u64 {
+    saturating_s(&utc_time())
+}
+
+/// Returns the current date and time in the configured timezone as an RFC 3339 string.
+///
+/// This is a shorthand for `tz_time().to_rfc3339()`, producing timestamps
+/// such as `2025-05-17T14:03:09.123456789+02:00` that are suitable for API
+/// responses and headers.
+///
+/// # Returns
+///
+/// A `String` holding the current local time in RFC 3339 / ISO 8601 format.
+///
+/// # Examples
+///
+/// ```
+/// let now = tz_time_rfc3339();
+/// assert!(parse_rfc3339(&now).is_some());
+/// ```
+pub fn tz_time_rfc3339() -> String {
+    tz_time().to_rfc3339()
+}
+
+/// Parses an RFC 3339 timestamp and converts it to the configured timezone.
+///
+/// The input may carry any UTC offset; the instant it denotes is preserved
+/// and only its representation is converted to the timezone returned by
+/// [`timezone`].
+///
+/// # Returns
+///
+/// `Some(DateTime<Tz>)` if `s` is a valid RFC 3339 timestamp, `None` otherwise.
+///
+/// # Examples
+///
+/// ```
+/// let parsed = parse_rfc3339("2025-05-17T12:00:00Z").unwrap();
+/// assert_eq!(parsed.timestamp(), 1747483200);
+/// assert!(parse_rfc3339("yesterday").is_none());
+/// ```
+pub fn parse_rfc3339(s: &str) -> Option<chrono::DateTime<Tz>> {
+    chrono::DateTime::parse_from_rfc3339(s)
+        .ok()
+        .map(|datetime| datetime.with_timezone(&timezone()))
+}
+
+/// Returns the number of milliseconds elapsed since `since`.
+///
+/// Unlike [`tz_time_ms`], this is based on the monotonic clock, so it is
+/// unaffected by system clock adjustments and suited for measuring durations
+/// such as request latency.
+///
+/// # Examples
+///
+/// ```
+/// let start = Instant::now();
+/// handle_request();
+/// log::debug!("Request handled in {} ms", elapsed_ms(start));
+/// ```
+pub fn elapsed_ms(since: Instant) -> u128 {
+    since.elapsed().as_millis()
+}
+
+/// Records the current instant as the moment the server started.
+///
+/// Meant to be called once, early in `main`; later calls have no effect.
+pub fn mark_started() {
+    STARTED.get_or_init(Instant::now);
+}
+
+/// Returns the number of milliseconds since [`mark_started`] was called, or
+/// since the first call to this function if it never was.
+///
+/// # Examples
+///
+/// ```
+/// log::info!("Up for {}", format_duration(uptime_ms()));
+/// ```
+pub fn uptime_ms() -> u64 {
+    let started = STARTED.get_or_init(Instant::now);
+    u64::try_from(elapsed_ms(*started)).unwrap_or(u64::MAX)
+}
+
+/// A monotonic stopwatch for measuring durations in milliseconds.
+///
+/// # Examples
+///
+/// ```
+/// let mut stopwatch = Stopwatch::start();
+/// run_migrations();
+/// log::info!("Migrations took {} ms", stopwatch.elapsed_ms());
+///
+/// stopwatch.reset();
+/// warm_caches();
+/// log::info!("Cache warm-up took {} ms", stopwatch.elapsed_ms());
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct Stopwatch {
+    started: Instant,
+}
+
+impl Stopwatch {
+    /// Creates a stopwatch that starts counting immediately.
+    pub fn start() -> Self {
+        Self {
+            started: Instant::now(),
+        }
+    }
+
+    /// Returns the number of milliseconds since the stopwatch was started or last reset.
+    pub fn elapsed_ms(&self) -> u128 {
+        elapsed_ms(self.started)
+    }
+
+    /// Restarts the measurement from the current instant.
+    pub fn reset(&mut self) {
+        self.started = Instant::now();
+    }
+}
+
+/// Splits `ms` into whole hours, minutes and seconds, dropping the remainder.
+fn duration_parts(ms: u64) -> [(u64, &'static str, &'static str); 3] {
+    let secs = ms / 1000;
+    [
+        (secs / 3600, "h", "hour"),
+        (secs / 60 % 60, "m", "minute"),
+        (secs % 60, "s", "second"),
+    ]
+}
+
+/// Formats a duration given in milliseconds as a compact string such as
+/// `1h2m3s`, `5m`, or `450ms`.
+///
+/// Durations under a second are shown in milliseconds; longer ones in whole
+/// hours, minutes and seconds, leaving out zero components and the
+/// sub-second remainder.
+///
+/// # Examples
+///
+/// ```
+/// assert_eq!(format_duration(0), "0ms");
+/// assert_eq!(format_duration(450), "450ms");
+/// assert_eq!(format_duration(3_723_000), "1h2m3s");
+/// ```
+pub fn format_duration(ms: u64) -> String {
+    if ms < 1000 {
+        return format!("{ms}ms");
+    }
+
+    duration_parts(ms)
+        .into_iter()
+        .filter(|(value, ..)| *value > 0)
+        .map(|(value, unit, _)| format!("{value}{unit}"))
+        .collect()
+}
+
+/// Formats a duration given in milliseconds in words, such as
+/// `1 hour 2 minutes` or `450 milliseconds`.
+///
+/// The same components as in [`format_duration`] are used, each spelled out
+/// and pluralized as needed.
+///
+/// # Examples
+///
+/// ```
+/// assert_eq!(format_duration_long(1000), "1 second");
+/// assert_eq!(format_duration_long(3_720_000), "1 hour 2 minutes");
+/// ```
+pub fn format_duration_long(ms: u64) -> String {
+    let plural = |value: u64| if value == 1 { "" } else { "s" };
+
+    if ms < 1000 {
+        return format!("{ms} millisecond{}", plural(ms));
+    }
+
+    duration_parts(ms)
+        .into_iter()
+        .filter(|(value, ..)| *value > 0)
+        .map(|(value, _, unit)| format!("{value} {unit}{}", plural(value)))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Offset;
+
+    use super::*;
+
+    #[test]
+    fn utc_timezone_has_zero_offset() {
+        let config = config::Config::from_vars(&[("FERROXIDE_TZ", "UTC")]);
+        assert_eq!(config.timezone, Tz::UTC);
+
+        let now = utc_time().with_timezone(&config.timezone);
+        assert_eq!(now.offset().fix().local_minus_utc(), 0);
+
+        let config = config::Config::from_vars(&[]);
+        assert_eq!(config.timezone, Tz::Europe__Warsaw);
+        let now = utc_time().with_timezone(&config.timezone);
+        assert_ne!(now.offset().fix().local_minus_utc(), 0);
+
+        let config = config::Config::from_vars(&[("FERROXIDE_TZ", "Nowhere/Special")]);
+        assert_eq!(config.timezone, Tz::Europe__Warsaw);
+        assert_eq!(config.problems.len(), 1);
+    }
+
+    #[test]
+    fn rfc3339_round_trips() {
+        let parsed = parse_rfc3339("2025-05-17T14:03:09.123+02:00").unwrap();
+        assert_eq!(parsed.timestamp_millis(), 1_747_483_389_123);
+        assert_eq!(parse_rfc3339(&parsed.to_rfc3339()), Some(parsed));
+        assert_eq!(parse_rfc3339("2025-05-17T12:03:09.123Z"), Some(parsed));
+
+        let now = tz_time_rfc3339();
+        assert_eq!(parse_rfc3339(&now).unwrap().to_rfc3339(), now);
+
+        assert_eq!(parse_rfc3339("2025-05-17 14:03:09"), None);
+        assert_eq!(parse_rfc3339("yesterday"), None);
+    }
+
+    #[test]
+    fn pre_epoch_timestamps_saturate_to_zero() {
+        let before = Utc.with_ymd_and_hms(1969, 7, 20, 20, 17, 40).unwrap();
+        assert_eq!(saturating_s(&before), 0);
+        assert_eq!(saturating_ms(&before), 0);
+
+        let before = before.with_timezone(&Tz::America__New_York);
+        assert_eq!(saturating_s(&before), 0);
+        assert_eq!(saturating_ms(&before), 0);
+
+        let epoch = DateTime::UNIX_EPOCH;
+        assert_eq!(
+            saturating_ms(&(epoch - chrono::Duration::milliseconds(1))),
+            0
+        );
+        assert_eq!(
+            saturating_ms(&(epoch + chrono::Duration::milliseconds(1))),
+            1
+        );
+        assert_eq!(saturating_s(&epoch), 0);
+    }
+
+    #[test]
+    fn stopwatch_measures_at_least_the_time_slept() {
+        let start = Instant::now();
+        let mut stopwatch = Stopwatch::start();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        assert!(stopwatch.elapsed_ms() >= 20);
+        assert!(elapsed_ms(start) >= stopwatch.elapsed_ms());
+
+        stopwatch.reset();
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        assert!(stopwatch.elapsed_ms() >= 5);
+        assert!(stopwatch.elapsed_ms() < elapsed_ms(start));
+    }
+
+    #[test]
+    fn utc_and_tz_seconds_agree() {
+        // Sampled in an order that brackets each other, in case a second
+        // boundary falls in between
+        let before = utc_time_s();
+        let tz = tz_time_s();
+        let after = utc_time_s();
+        assert!(before <= tz && tz <= after, "{before} {tz} {after}");
+        assert!(after - before <= 1);
+    }
+
+    #[test]
+    fn durations_are_formatted_compactly_and_in_words() {
+        assert_eq!(format_duration(0), "0ms");
+        assert_eq!(format_duration_long(0), "0 milliseconds");
+
+        assert_eq!(format_duration(1), "1ms");
+        assert_eq!(format_duration_long(1), "1 millisecond");
+        assert_eq!(format_duration(999), "999ms");
+        assert_eq!(format_duration_long(450), "450 milliseconds");
+
+        assert_eq!(format_duration(1000), "1s");
+        assert_eq!(format_duration(60_500), "1m");
+        assert_eq!(format_duration_long(61_000), "1 minute 1 second");
+
+        assert_eq!(format_duration(3_723_000), "1h2m3s");
+        assert_eq!(format_duration(3_600_999), "1h");
+        assert_eq!(format_duration(90_000_000), "25h");
+        assert_eq!(format_duration_long(3_720_000), "1 hour 2 minutes");
+        assert_eq!(format_duration_long(7_202_000), "2 hours 2 seconds");
+    }
 }
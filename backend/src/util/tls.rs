@@ -0,0 +1,45 @@
+use std::io;
+use std::path::Path;
+
+use rustls::ServerConfig;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+
+/// Builds a rustls server configuration from PEM encoded files.
+///
+/// `cert` may contain a whole chain, leaf certificate first. `key` must hold
+/// the matching private key in PKCS#1, PKCS#8 or SEC1 form.
+///
+/// # Errors
+///
+/// Returns an error if either file cannot be read or parsed, if `cert` holds
+/// no certificates, or if the key does not match the certificate.
+///
+/// # Examples
+///
+/// ```
+/// let config = load_tls_config("cert.pem".as_ref(), "key.pem".as_ref())?;
+/// HttpServer::new(app).bind_rustls_0_23(addr, config)?;
+/// ```
+pub fn load_tls_config(cert: &Path, key: &Path) -> io::Result<ServerConfig> {
+    let certs = CertificateDer::pem_file_iter(cert)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|err| io::Error::other(format!("{}: {err}", cert.display())))?;
+
+    if certs.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}: no certificates found", cert.display()),
+        ));
+    }
+
+    let key = PrivateKeyDer::from_pem_file(key)
+        .map_err(|err| io::Error::other(format!("{}: {err}", key.display())))?;
+
+    ServerConfig::builder_with_provider(rustls::crypto::ring::default_provider().into())
+        .with_safe_default_protocol_versions()
+        .map_err(io::Error::other)?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(io::Error::other)
+}
@@ -0,0 +1,144 @@
+use std::collections::{HashMap, HashSet};
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use actix_ws::{CloseCode, Session};
+
+/// Identifies a connected WebSocket session within the [`Hub`].
+pub type SessionId = u64;
+
+#[derive(Default)]
+struct Registry {
+    sessions: HashMap<SessionId, Session>,
+    rooms: HashMap<String, HashSet<SessionId>>,
+}
+
+/// Registry of connected WebSocket sessions and the rooms they joined.
+///
+/// The hub is shared between workers as `web::Data<Hub>`, so handlers can fan
+/// out a message to every session in a room regardless of which worker owns
+/// the connection.
+#[derive(Default)]
+pub struct Hub {
+    next_id: AtomicU64,
+    registry: RwLock<Registry>,
+    /// Live connections, counted from the upgrade on, before they register.
+    connections: AtomicUsize,
+}
+
+impl Hub {
+    /// Counts a new connection unless `max` connections are already live, and
+    /// returns whether it was counted.
+    pub fn reserve(&self, max: Option<NonZeroUsize>) -> bool {
+        self.connections
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |count| match max {
+                Some(max) if count >= max.get() => None,
+                _ => Some(count + 1),
+            })
+            .is_ok()
+    }
+
+    /// Stops counting a connection counted by [`Hub::reserve`].
+    pub fn release(&self) {
+        self.connections.fetch_sub(1, Ordering::AcqRel);
+    }
+
+    /// Adds a session to the hub and returns the id it is known by.
+    pub fn register(&self, session: Session) -> SessionId {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.write().sessions.insert(id, session);
+        id
+    }
+
+    /// Removes a session from the hub and from every room it joined.
+    pub fn unregister(&self, id: SessionId) {
+        let mut registry = self.write();
+        registry.sessions.remove(&id);
+        registry.rooms.retain(|_, members| {
+            members.remove(&id);
+            !members.is_empty()
+        });
+    }
+
+    /// Adds a registered session to `room`, creating the room if needed.
+    pub fn join(&self, room: &str, id: SessionId) {
+        let mut registry = self.write();
+        if registry.sessions.contains_key(&id) {
+            registry
+                .rooms
+                .entry(room.to_owned())
+                .or_default()
+                .insert(id);
+        }
+    }
+
+    /// Removes a session from `room`, dropping the room once it is empty.
+    pub fn leave(&self, room: &str, id: SessionId) {
+        let mut registry = self.write();
+        if let Some(members) = registry.rooms.get_mut(room) {
+            members.remove(&id);
+            if members.is_empty() {
+                registry.rooms.remove(room);
+            }
+        }
+    }
+
+    /// Sends a text message to every session in `room`.
+    ///
+    /// Sessions whose connection turns out to be closed are unregistered.
+    /// Returns the number of sessions the message was delivered to.
+    pub async fn broadcast(&self, room: &str, msg: &str) -> usize {
+        // Clone the handles so that no lock is held across the sends
+        let sessions = {
+            let registry = self.read();
+            let Some(members) = registry.rooms.get(room) else {
+                return 0;
+            };
+
+            members
+                .iter()
+                .filter_map(|id| Some((*id, registry.sessions.get(id)?.clone())))
+                .collect::<Vec<_>>()
+        };
+
+        let mut delivered = 0;
+        for (id, mut session) in sessions {
+            match session.text(msg.to_owned()).await {
+                Ok(()) => delivered += 1,
+                Err(_) => self.unregister(id),
+            }
+        }
+
+        delivered
+    }
+
+    /// Closes every session with status 1001 (going away) and empties the hub.
+    ///
+    /// Meant to be called on shutdown, so clients learn that the server is
+    /// going down and can reconnect elsewhere instead of seeing the connection
+    /// drop.
+    pub async fn close_all(&self) {
+        let sessions = {
+            let mut registry = self.write();
+            registry.rooms.clear();
+            registry.sessions.drain().collect::<Vec<_>>()
+        };
+
+        if !sessions.is_empty() {
+            log::info!("Closing {} WebSocket session(s)", sessions.len());
+        }
+
+        for (_, session) in sessions {
+            let _ = session.close(Some(CloseCode::Away.into())).await;
+        }
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, Registry> {
+        self.registry.read().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Registry> {
+        self.registry.write().unwrap_or_else(|err| err.into_inner())
+    }
+}
@@ -0,0 +1,578 @@
+use std::num::NonZeroUsize;
+
+use actix_web::http::header;
+use actix_web::{HttpRequest, HttpResponse, get, rt, web};
+use actix_ws::{CloseCode, CloseReason, Item, Message, MessageStream, ProtocolError, Session};
+use futures_util::StreamExt;
+use serde::Deserialize;
+use sqlx::SqlitePool;
+
+use super::{Hub, SessionId};
+use crate::routes::ApiError;
+use crate::util::tz_time_ms;
+use crate::{config, database};
+
+/// Seconds a client refused for being over the connection limit should wait.
+const RETRY_AFTER: u64 = 5;
+/// Setting holding the token clients must present, see [`connect`].
+const TOKEN_SETTING: &str = "ws_token";
+
+/// A reserved slot in the hub's live connection count, released on drop.
+struct ConnectionSlot(web::Data<Hub>);
+
+impl ConnectionSlot {
+    /// Reserves a slot, or returns `None` if `max` connections are already live.
+    fn acquire(hub: &web::Data<Hub>, max: Option<NonZeroUsize>) -> Option<Self> {
+        hub.reserve(max).then(|| ConnectionSlot(hub.clone()))
+    }
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.0.release();
+    }
+}
+
+#[derive(Deserialize)]
+struct ConnectQuery {
+    room: String,
+    token: Option<String>,
+}
+
+/// Returns the token clients must present, preferring the `ws_token` setting
+/// over `FERROXIDE_WS_TOKEN` so it can be rotated without a restart.
+async fn expected_token(
+    pool: &SqlitePool,
+    config: &config::WsConfig,
+) -> Result<Option<String>, sqlx::Error> {
+    match database::get_setting(pool, TOKEN_SETTING).await? {
+        Some(token) if !token.is_empty() => Ok(Some(token)),
+        _ => Ok(config.token.clone()),
+    }
+}
+
+/// Returns the token sent in the `token` query parameter or, failing that, as
+/// an `Authorization: Bearer` header.
+fn presented_token<'a>(req: &'a HttpRequest, query: &'a ConnectQuery) -> Option<&'a str> {
+    if let Some(token) = &query.token {
+        return Some(token);
+    }
+
+    req.headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim)
+}
+
+/// Compares two tokens in time independent of where they first differ.
+fn tokens_match(expected: &str, presented: &str) -> bool {
+    expected.len() == presented.len()
+        && expected
+            .bytes()
+            .zip(presented.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Upgrades the connection to a WebSocket session in the `room` given as a
+/// query parameter (e.g. `/ws?room=general`).
+///
+/// Every text frame the client sends is stored in the database and broadcast
+/// to all sessions in the room, including the sender. On connecting, the
+/// client first receives the last `FERROXIDE_WS_HISTORY` messages of the room
+/// (20 by default, 0 disables this), oldest first.
+///
+/// The server pings the client every `FERROXIDE_WS_PING_INTERVAL` seconds (5 by
+/// default) and closes the connection if nothing, not even a pong, was
+/// received for `FERROXIDE_WS_TIMEOUT` seconds (10 by default). This keeps
+/// clients that vanished without a close frame from leaking sockets.
+///
+/// When a token is configured, through the `ws_token` setting or the
+/// `FERROXIDE_WS_TOKEN` environment variable (the setting wins), clients must
+/// present it as a `token` query parameter or an `Authorization: Bearer`
+/// header; otherwise the upgrade is refused with `401 Unauthorized`. Without
+/// a configured token anyone may connect.
+///
+/// If `FERROXIDE_WS_MAX_CONNECTIONS` is set and that many sessions are already
+/// live, the upgrade is refused with `503 Service Unavailable` and a
+/// `Retry-After` header.
+///
+/// Messages larger than `FERROXIDE_WS_MAX_FRAME` bytes (64 KiB by default),
+/// whether sent as a single frame or split into continuation frames, close the
+/// connection with status 1009 (message too big).
+#[get("/ws")]
+pub async fn connect(
+    req: HttpRequest,
+    body: web::Payload,
+    query: web::Query<ConnectQuery>,
+    hub: web::Data<Hub>,
+    pool: web::Data<SqlitePool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    upgrade(req, body, query, hub, pool, &config::get().ws).await
+}
+
+/// Handles [`connect`] with the given settings.
+async fn upgrade(
+    req: HttpRequest,
+    body: web::Payload,
+    query: web::Query<ConnectQuery>,
+    hub: web::Data<Hub>,
+    pool: web::Data<SqlitePool>,
+    config: &'static config::WsConfig,
+) -> Result<HttpResponse, actix_web::Error> {
+    if let Some(expected) = expected_token(&pool, config)
+        .await
+        .map_err(ApiError::from)?
+    {
+        let authorized = presented_token(&req, &query)
+            .is_some_and(|presented| tokens_match(&expected, presented));
+
+        if !authorized {
+            log::warn!("Refusing WebSocket upgrade with a missing or invalid token");
+            return Err(ApiError::Unauthorized.into());
+        }
+    }
+
+    let Some(slot) = ConnectionSlot::acquire(&hub, config.max_connections) else {
+        log::warn!("WebSocket connection limit reached; refusing upgrade");
+        return Ok(HttpResponse::ServiceUnavailable()
+            .insert_header((header::RETRY_AFTER, RETRY_AFTER))
+            .finish());
+    };
+
+    let (res, mut session, stream) = actix_ws::handle(&req, body)?;
+    let room = query.into_inner().room;
+
+    let id = hub.register(session.clone());
+
+    rt::spawn(async move {
+        // Only join once the backlog is out, so live messages follow it
+        if send_history(&mut session, &pool, &room, config.history)
+            .await
+            .is_ok()
+        {
+            hub.join(&room, id);
+            run(session, stream, &hub, &pool, id, &room, config).await;
+            hub.leave(&room, id);
+        }
+
+        hub.unregister(id);
+        drop(slot);
+    });
+
+    Ok(res)
+}
+
+/// Sends the last `limit` messages of `room` to a newly connected client.
+/// Failing to load them is logged and otherwise ignored; an error is only
+/// returned if the client is already gone.
+async fn send_history(
+    session: &mut Session,
+    pool: &SqlitePool,
+    room: &str,
+    limit: usize,
+) -> Result<(), actix_ws::Closed> {
+    if limit == 0 {
+        return Ok(());
+    }
+
+    let history = match database::recent_messages(pool, room, limit).await {
+        Ok(history) => history,
+        Err(err) => {
+            log::error!("Failed to load WebSocket message history: {err}");
+            return Ok(());
+        }
+    };
+
+    for payload in history {
+        session.text(payload).await?;
+    }
+
+    Ok(())
+}
+
+fn too_big() -> Option<CloseReason> {
+    Some(CloseReason {
+        code: CloseCode::Size,
+        description: Some("message too big".to_owned()),
+    })
+}
+
+async fn run(
+    mut session: Session,
+    stream: MessageStream,
+    hub: &Hub,
+    pool: &SqlitePool,
+    id: SessionId,
+    room: &str,
+    config: &config::WsConfig,
+) {
+    let timeout = u64::try_from(config.timeout.as_millis()).unwrap_or(u64::MAX);
+    let max_frame = config.max_frame.get();
+
+    let mut stream = stream.max_frame_size(max_frame);
+    let mut continuation = 0;
+
+    let mut last_seen = tz_time_ms();
+    let mut heartbeat = tokio::time::interval(config.ping_interval);
+
+    let reason = loop {
+        tokio::select! {
+            msg = stream.next() => {
+                let msg = match msg {
+                    Some(Ok(msg)) => msg,
+                    Some(Err(ProtocolError::Overflow)) => break too_big(),
+                    Some(Err(err)) => {
+                        log::warn!("WebSocket protocol error: {err}");
+                        break None;
+                    }
+                    None => break None,
+                };
+
+                last_seen = tz_time_ms();
+                match msg {
+                    Message::Ping(bytes) => {
+                        if session.pong(&bytes).await.is_err() {
+                            return;
+                        }
+                    }
+                    Message::Text(text) => {
+                        if let Err(err) = database::insert_message(pool, id, room, &text, last_seen).await {
+                            log::error!("Failed to store WebSocket message: {err}");
+                        }
+
+                        hub.broadcast(room, &text).await;
+                    }
+                    Message::Continuation(item) => {
+                        let (len, last) = match item {
+                            Item::FirstText(bytes) | Item::FirstBinary(bytes) => {
+                                continuation = 0;
+                                (bytes.len(), false)
+                            }
+                            Item::Continue(bytes) => (bytes.len(), false),
+                            Item::Last(bytes) => (bytes.len(), true),
+                        };
+
+                        continuation += len;
+                        if continuation > max_frame {
+                            break too_big();
+                        }
+
+                        if last {
+                            continuation = 0;
+                        }
+                    }
+                    Message::Close(reason) => break reason,
+                    _ => {}
+                }
+            }
+            _ = heartbeat.tick() => {
+                if tz_time_ms().saturating_sub(last_seen) > timeout {
+                    log::info!("WebSocket client timed out; closing connection");
+                    break None;
+                }
+
+                if session.ping(b"").await.is_err() {
+                    return;
+                }
+            }
+        }
+    };
+
+    let _ = session.close(reason).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use actix_web::App;
+    use awc::ws::Frame;
+    use futures_util::SinkExt;
+
+    use super::*;
+
+    /// Starts a server whose `/ws` endpoint uses `config`, returning it along
+    /// with its database.
+    async fn start(config: config::WsConfig) -> (actix_test::TestServer, SqlitePool) {
+        let config: &'static config::WsConfig = Box::leak(Box::new(config));
+        let pool = database::memory_pool().await;
+        let data = web::Data::new(pool.clone());
+        let hub = web::Data::new(Hub::default());
+
+        let server = actix_test::start(move || {
+            App::new()
+                .app_data(data.clone())
+                .app_data(hub.clone())
+                .route(
+                    "/ws",
+                    web::get().to(
+                        move |req: HttpRequest,
+                              body: web::Payload,
+                              query: web::Query<ConnectQuery>,
+                              hub: web::Data<Hub>,
+                              pool: web::Data<SqlitePool>| {
+                            upgrade(req, body, query, hub, pool, config)
+                        },
+                    ),
+                )
+        });
+
+        (server, pool)
+    }
+
+    /// The default settings, as if no variable was set.
+    fn ws_config() -> config::WsConfig {
+        config::Config::from_vars(&[]).ws
+    }
+
+    type Client = actix_codec::Framed<awc::BoxedSocket, awc::ws::Codec>;
+
+    /// Opens a WebSocket connection to `path` on `server`.
+    async fn ws(server: &actix_test::TestServer, path: &str) -> Client {
+        let (_, client) = awc::Client::new()
+            .ws(server.url(path))
+            .connect()
+            .await
+            .unwrap();
+        client
+    }
+
+    /// Returns the next text message `client` receives, skipping anything else.
+    async fn next_text(client: &mut Client) -> String {
+        let next = async {
+            loop {
+                match client.next().await {
+                    Some(Ok(Frame::Text(text))) => break String::from_utf8(text.to_vec()).unwrap(),
+                    Some(Ok(Frame::Close(reason))) => panic!("closed: {reason:?}"),
+                    Some(Ok(_)) => {}
+                    Some(Err(err)) => panic!("{err}"),
+                    None => panic!("connection dropped"),
+                }
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(5), next)
+            .await
+            .expect("no message received")
+    }
+
+    /// Sends `text` and waits for it to come back, which proves `client`
+    /// joined its room.
+    async fn send_text(client: &mut Client, text: &str) {
+        client
+            .send(awc::ws::Message::Text(text.to_owned().into()))
+            .await
+            .unwrap();
+        while next_text(client).await != text {}
+    }
+
+    #[actix_web::test]
+    async fn silent_clients_are_disconnected() {
+        let mut config = ws_config();
+        config.ping_interval = Duration::from_millis(50);
+        config.timeout = Duration::from_millis(200);
+        let (server, _pool) = start(config).await;
+
+        // The client never answers the pings it receives
+        let mut client = ws(&server, "/ws?room=general").await;
+        let mut pings = 0;
+        let closed = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                match client.next().await {
+                    Some(Ok(Frame::Ping(_))) => pings += 1,
+                    Some(Ok(Frame::Close(_))) | None => break,
+                    Some(Ok(_)) => {}
+                    Some(Err(err)) => panic!("{err}"),
+                }
+            }
+        })
+        .await;
+
+        assert!(closed.is_ok(), "connection was not closed");
+        assert!(pings > 1);
+    }
+
+    #[actix_web::test]
+    async fn broadcasts_reach_every_session_in_the_room() {
+        let (server, _pool) = start(ws_config()).await;
+
+        let mut alice = ws(&server, "/ws?room=general").await;
+        let mut bob = ws(&server, "/ws?room=general").await;
+        let mut carol = ws(&server, "/ws?room=random").await;
+        send_text(&mut alice, "alice joined").await;
+        send_text(&mut bob, "bob joined").await;
+        send_text(&mut carol, "carol joined").await;
+
+        send_text(&mut alice, "hello").await;
+        assert_eq!(next_text(&mut bob).await, "hello");
+
+        send_text(&mut carol, "elsewhere").await;
+        bob.send(awc::ws::Message::Text("bye".into()))
+            .await
+            .unwrap();
+        assert_eq!(next_text(&mut alice).await, "bye");
+    }
+
+    #[actix_web::test]
+    async fn connections_over_the_limit_are_refused() {
+        let mut config = ws_config();
+        config.max_connections = NonZeroUsize::new(2);
+        let (server, _pool) = start(config).await;
+
+        let first = ws(&server, "/ws?room=general").await;
+        let _second = ws(&server, "/ws?room=general").await;
+
+        let refused = awc::Client::new()
+            .ws(server.url("/ws?room=general"))
+            .connect()
+            .await;
+        match refused {
+            Err(awc::error::WsClientError::InvalidResponseStatus(status)) => {
+                assert_eq!(status, 503);
+            }
+            res => panic!("expected a refusal, got {:?}", res.map(|(res, _)| res)),
+        }
+
+        // Hanging up frees the slot once the server notices
+        drop(first);
+        let reconnected = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let res = awc::Client::new()
+                    .ws(server.url("/ws?room=general"))
+                    .connect()
+                    .await;
+                if res.is_ok() {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await;
+        assert!(reconnected.is_ok());
+    }
+
+    /// Waits for `client` to be closed and returns the close code it got.
+    async fn close_code(client: &mut Client) -> Option<CloseCode> {
+        let closed = async {
+            loop {
+                match client.next().await {
+                    Some(Ok(Frame::Close(reason))) => break reason.map(|reason| reason.code),
+                    Some(Ok(_)) => {}
+                    Some(Err(_)) | None => break None,
+                }
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(5), closed)
+            .await
+            .expect("connection was not closed")
+    }
+
+    #[actix_web::test]
+    async fn oversized_messages_close_the_connection() {
+        let mut config = ws_config();
+        config.max_frame = NonZeroUsize::new(1024).unwrap();
+        let (server, _pool) = start(config).await;
+
+        let mut client = ws(&server, "/ws?room=general").await;
+        send_text(&mut client, &"x".repeat(1024)).await;
+        client
+            .send(awc::ws::Message::Text("x".repeat(1025).into()))
+            .await
+            .unwrap();
+        assert_eq!(close_code(&mut client).await, Some(CloseCode::Size));
+
+        // Continuation frames count towards the limit together
+        let mut client = ws(&server, "/ws?room=general").await;
+        let half = web::Bytes::from("x".repeat(600));
+        client
+            .send(awc::ws::Message::Continuation(Item::FirstText(
+                half.clone(),
+            )))
+            .await
+            .unwrap();
+        client
+            .send(awc::ws::Message::Continuation(Item::Last(half)))
+            .await
+            .unwrap();
+        assert_eq!(close_code(&mut client).await, Some(CloseCode::Size));
+    }
+
+    #[actix_web::test]
+    async fn messages_are_stored() {
+        let (server, pool) = start(ws_config()).await;
+
+        let mut client = ws(&server, "/ws?room=general").await;
+        send_text(&mut client, "hello").await;
+
+        let (room, payload, timestamp): (String, String, i64) =
+            sqlx::query_as("SELECT room, payload, timestamp FROM websocket_messages")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(room, "general");
+        assert_eq!(payload, "hello");
+        assert!(timestamp.abs_diff(tz_time_ms() as i64) < 60_000);
+    }
+
+    #[actix_web::test]
+    async fn upgrades_require_the_configured_token() {
+        let mut config = ws_config();
+        config.token = Some("secret".to_owned());
+        let (server, pool) = start(config).await;
+
+        let status = |path: &str, authorization: Option<&str>| {
+            let mut req = awc::Client::new().ws(server.url(path));
+            if let Some(authorization) = authorization {
+                req = req.header(header::AUTHORIZATION, authorization);
+            }
+            async move {
+                match req.connect().await {
+                    Ok((res, _)) => res.status().as_u16(),
+                    Err(awc::error::WsClientError::InvalidResponseStatus(status)) => {
+                        status.as_u16()
+                    }
+                    Err(err) => panic!("{err}"),
+                }
+            }
+        };
+
+        assert_eq!(status("/ws?room=general", None).await, 401);
+        assert_eq!(status("/ws?room=general&token=wrong", None).await, 401);
+        assert_eq!(status("/ws?room=general", Some("Bearer wrong")).await, 401);
+        assert_eq!(status("/ws?room=general&token=secret", None).await, 101);
+        assert_eq!(status("/ws?room=general", Some("Bearer secret")).await, 101);
+
+        // The setting takes precedence over the configured token
+        database::set_setting(&pool, TOKEN_SETTING, "rotated")
+            .await
+            .unwrap();
+        assert_eq!(status("/ws?room=general&token=secret", None).await, 401);
+        assert_eq!(status("/ws?room=general&token=rotated", None).await, 101);
+    }
+
+    #[actix_web::test]
+    async fn newcomers_receive_the_room_history() {
+        let mut config = ws_config();
+        config.history = 2;
+        let (server, _pool) = start(config).await;
+
+        let mut sender = ws(&server, "/ws?room=general").await;
+        for text in ["one", "two", "three"] {
+            send_text(&mut sender, text).await;
+        }
+        let mut elsewhere = ws(&server, "/ws?room=random").await;
+        send_text(&mut elsewhere, "elsewhere").await;
+
+        // The last two messages of the room, oldest first, then live traffic
+        let mut newcomer = ws(&server, "/ws?room=general").await;
+        assert_eq!(next_text(&mut newcomer).await, "two");
+        assert_eq!(next_text(&mut newcomer).await, "three");
+
+        newcomer
+            .send(awc::ws::Message::Text("four".into()))
+            .await
+            .unwrap();
+        assert_eq!(next_text(&mut newcomer).await, "four");
+    }
+}